@@ -1,19 +1,25 @@
 pub(crate) use std::{
   borrow::Cow,
+  cmp::Reverse,
+  collections::{BTreeMap, BTreeSet, HashMap},
+  env,
   ffi::OsStr,
   fmt::{self, Display, Formatter},
-  fs::{self, File},
-  io::{self, prelude::*},
-  path::PathBuf,
+  fs,
+  io::{self, prelude::*, IsTerminal},
+  iter,
+  ops::Range,
+  path::{Path, PathBuf},
   process::{self, Command},
   str,
   sync::Arc,
+  time::SystemTime,
 };
 
 pub(crate) use {
-  clap::Parser,
+  chrono::{DateTime, Local, NaiveDate, Utc},
+  clap::{Parser, ValueEnum},
   colored::Colorize,
-  indoc::indoc,
   serde::{Deserialize, Serialize},
   skim::prelude::*,
   snafu::Snafu,
@@ -21,17 +27,45 @@ pub(crate) use {
 };
 
 pub(crate) use crate::{
-  config::Config, directory::Directory, error::Error, handler::Handler,
-  matter::Matter, note::Note, note_id::NoteId, opt::Opt, search::Search,
+  config::Config,
+  config_command::ConfigCommand,
+  directory::Directory,
+  doctor::Problem,
+  editor::{Editor, Location},
+  error::Error,
+  format::Format,
+  handler::Handler,
+  history::{Change, History},
+  id_scheme::IdScheme,
+  index::Index,
+  journal::{Journal, Period},
+  link::{self, Link, LinkKind, Names},
+  listing::{ListSort, Listing},
+  matter::Matter,
+  new_note::NewNote,
+  note::Note,
+  note_id::NoteId,
+  opt::Opt,
+  query::Query,
+  retag_command::RetagCommand,
+  search::Search,
+  selection::Selection,
+  sequence::{Sequence, SequenceTree},
+  store,
+  subcommand::Subcommand,
+  tag_summary::{TagSort, TagSummary},
+  tag_tree::TagTree,
+  template::Template,
+  text_index::TextIndex,
+  timestamp,
+  trash::{RemovedLink, Trash},
+  trash_command::TrashCommand,
+  tree::Tree,
 };
 
-pub(crate) use crate::path_ext::PathExt;
+pub(crate) use crate::{path_ext::PathExt, tag_ext::TagExt};
 
 pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[cfg(test)]
-pub(crate) use {
-  crate::test_utils::*,
-  std::{env, thread, time},
-  tempfile::TempDir,
-};
+pub(crate) use {crate::test_utils::*, indoc::indoc, tempfile::TempDir};
@@ -1,23 +1,119 @@
-use crate::common::*;
+use {
+  crate::common::*,
+  toml::{value::Table, Value},
+};
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub(crate) struct Config {
   pub(crate) path: PathBuf,
   pub(crate) editor: String,
+  pub(crate) id_scheme: IdScheme,
+  /// Where templates for `zk new --template` are kept, besides the vault's
+  /// own `.zk/templates`
+  pub(crate) templates: PathBuf,
+  pub(crate) journal: Journal,
+  /// The vault used unless `--vault`, `--dir` or a project configuration
+  /// says otherwise
+  pub(crate) vault: Option<String>,
+  /// Named vaults, which `--vault` selects and cross-vault links such as
+  /// `work:1625104269-a` refer to
+  pub(crate) vaults: BTreeMap<String, PathBuf>,
+  /// Where each effective value came from, keyed by its dotted name
+  #[serde(skip)]
+  pub(crate) sources: BTreeMap<String, (Value, Source)>,
 }
 
-impl Config {
-  fn default() -> &'static str {
-    indoc! {"
-      path   = '~/.zk'
-      editor = 'vim'
-    "}
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      path: "~/.zk".into(),
+      editor: "vim".into(),
+      id_scheme: IdScheme::default(),
+      templates: dirs::config_dir()
+        .unwrap_or_default()
+        .join("zk")
+        .join("templates"),
+      journal: Journal::default(),
+      vault: None,
+      vaults: BTreeMap::new(),
+      sources: BTreeMap::new(),
+    }
+  }
+}
+
+/// A place a configuration value can come from, from lowest to highest
+/// precedence.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Source {
+  Default,
+  /// `$VISUAL` or `$EDITOR`, used for `editor`
+  Editor(String),
+  User(PathBuf),
+  Project(PathBuf),
+  /// A `ZK_*` environment variable
+  Environment(String),
+  Flag(&'static str),
+}
+
+impl Display for Source {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Source::Default => write!(f, "default"),
+      Source::Editor(var) | Source::Environment(var) => write!(f, "${}", var),
+      Source::User(path) => write!(f, "user config {}", path.display()),
+      Source::Project(path) => write!(f, "project config {}", path.display()),
+      Source::Flag(flag) => write!(f, "{}", flag),
+    }
   }
+}
+
+/// The settings from a single source.
+struct Layer {
+  source: Source,
+  table: Table,
+}
+
+impl Layer {
+  fn value(source: Source, key: &str, value: &str) -> Self {
+    let mut table = Table::new();
+    Config::insert(&mut table, key, Value::String(value.to_owned()));
+    Self { source, table }
+  }
+
+  fn file(source: Source, path: &Path) -> Result<Self> {
+    let table =
+      toml::from_str(&fs::read_to_string(path)?).map_err(|source| {
+        Error::ConfigInvalid {
+          path: path.to_owned(),
+          source,
+        }
+      })?;
+
+    Ok(Self { source, table })
+  }
+}
+
+impl Config {
+  /// Every setting, by its dotted name, in the order `zk config show` lists
+  /// them.
+  pub(crate) const KEYS: &'static [&'static str] = &[
+    "path",
+    "editor",
+    "id_scheme",
+    "templates",
+    "journal.daily",
+    "journal.weekly",
+    "journal.daily_template",
+    "journal.weekly_template",
+    "vault",
+  ];
 
   fn filename() -> &'static str {
     ".zk.toml"
   }
 
+  /// The user configuration file, if there is one.
   fn path() -> Result<Option<PathBuf>> {
     Ok(
       xdg::BaseDirectories::with_prefix(dirs::home_dir().unwrap_or_default())?
@@ -25,25 +121,465 @@ impl Config {
     )
   }
 
-  pub(crate) fn load() -> Result<Self> {
-    if let Some(path) = Self::path()? {
-      let content = fs::read_to_string(&path)?;
-      Ok(toml::from_str(&content)?)
-    } else {
-      Ok(toml::from_str(Self::default())?)
+  pub(crate) fn editor(&self) -> Result<Editor> {
+    Editor::parse(&self.editor)
+  }
+
+  /// Load the configuration, layering the built-in defaults, the user
+  /// configuration, the nearest `.zk.toml` above the current directory,
+  /// `ZK_*` environment variables and finally `flags`, given on the command
+  /// line as `(flag, key, value)`.
+  pub(crate) fn load(flags: &[(&'static str, &str, String)]) -> Result<Self> {
+    Self::layered(
+      Self::path()?,
+      &env::current_dir()?,
+      |name| env::var(name).ok(),
+      flags,
+    )
+  }
+
+  fn layered(
+    user: Option<PathBuf>,
+    cwd: &Path,
+    var: impl Fn(&str) -> Option<String>,
+    flags: &[(&'static str, &str, String)],
+  ) -> Result<Self> {
+    let mut layers = vec![Layer {
+      source: Source::Default,
+      table: match Value::try_from(Config::default())? {
+        Value::Table(table) => table,
+        _ => Table::new(),
+      },
+    }];
+
+    if let Some((name, editor)) = ["VISUAL", "EDITOR"].iter().find_map(|name| {
+      var(name)
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| (name, value))
+    }) {
+      layers.push(Layer::value(
+        Source::Editor(name.to_string()),
+        "editor",
+        &editor,
+      ));
+    }
+
+    if let Some(path) = &user {
+      layers.push(Layer::file(Source::User(path.clone()), path)?);
+    }
+
+    let project = cwd
+      .ancestors()
+      .map(|dir| dir.join(Self::filename()))
+      .find(|path| path.is_file())
+      .filter(|path| Some(path) != user.as_ref());
+
+    if let Some(path) = project {
+      let mut layer = Layer::file(Source::Project(path.clone()), &path)?;
+      Self::resolve(&mut layer.table, path.parent().unwrap_or(cwd));
+      layers.push(layer);
+    }
+
+    for key in Self::KEYS {
+      let name = format!("ZK_{}", key.replace('.', "_").to_uppercase());
+
+      if let Some(value) = var(&name) {
+        layers.push(Layer::value(Source::Environment(name), key, &value));
+      }
+    }
+
+    for (flag, key, value) in flags {
+      layers.push(Layer::value(Source::Flag(flag), key, value));
+    }
+
+    let mut merged = Table::new();
+    let mut sources = BTreeMap::new();
+
+    for layer in layers {
+      for (key, value) in Self::flatten(&layer.table, "") {
+        Self::insert(&mut merged, &key, value.clone());
+        sources.insert(key, (value, layer.source.clone()));
+      }
+    }
+
+    Ok(Self {
+      sources,
+      ..Value::Table(merged).try_into()?
+    })
+  }
+
+  /// The directory of the vault in use: the `vault`, unless `path` was set
+  /// somewhere that takes precedence.
+  pub(crate) fn vault_path(&self) -> Result<PathBuf> {
+    let source = |key: &str| self.sources.get(key).map(|(_, source)| source);
+
+    match &self.vault {
+      Some(name) if source("vault") >= source("path") => self
+        .vaults
+        .get(name)
+        .map(PathExt::expand)
+        .ok_or_else(|| Error::VaultNotFound { name: name.clone() }),
+      _ => Ok(self.path.expand()),
+    }
+  }
+
+  /// Make the paths in a project configuration relative to the directory it
+  /// is in, which is also the vault unless it says otherwise.
+  fn resolve(table: &mut Table, dir: &Path) {
+    table
+      .entry("path")
+      .or_insert_with(|| Value::String(".".into()));
+
+    let mut paths = Vec::new();
+
+    for (key, value) in table.iter_mut() {
+      match (key.as_str(), value) {
+        ("path" | "templates", value) => paths.push(value),
+        ("vaults", Value::Table(vaults)) => {
+          paths.extend(vaults.iter_mut().map(|(_, value)| value))
+        }
+        _ => {}
+      }
+    }
+
+    for value in paths {
+      if let Value::String(path) = value {
+        if !path.starts_with('~') && Path::new(path).is_relative() {
+          *path = dir
+            .join(&path)
+            .components()
+            .collect::<PathBuf>()
+            .display()
+            .to_string();
+        }
+      }
     }
   }
+
+  /// Every leaf value in `table`, by its dotted name.
+  fn flatten(table: &Table, prefix: &str) -> Vec<(String, Value)> {
+    table
+      .iter()
+      .flat_map(|(key, value)| {
+        let key = format!("{}{}", prefix, key);
+
+        match value {
+          Value::Table(table) => Self::flatten(table, &format!("{}.", key)),
+          value => vec![(key, value.clone())],
+        }
+      })
+      .collect()
+  }
+
+  fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+      Some((head, rest)) => {
+        let entry = table
+          .entry(head)
+          .or_insert_with(|| Value::Table(Table::new()));
+
+        if !entry.is_table() {
+          *entry = Value::Table(Table::new());
+        }
+
+        if let Value::Table(table) = entry {
+          Self::insert(table, rest, value);
+        }
+      }
+      None => {
+        table.insert(key.to_owned(), value);
+      }
+    }
+  }
+
+  /// Print every setting with its effective value and where it came from.
+  pub(crate) fn show(&self, out: &mut impl Write) -> io::Result<()> {
+    let vaults = self
+      .sources
+      .keys()
+      .map(String::as_str)
+      .filter(|key| key.starts_with("vaults."));
+
+    let unknown = self
+      .sources
+      .keys()
+      .map(String::as_str)
+      .filter(|key| !Self::KEYS.contains(key) && !key.starts_with("vaults."));
+
+    let width = Self::KEYS
+      .iter()
+      .copied()
+      .chain(vaults.clone())
+      .chain(unknown.clone())
+      .map(str::len)
+      .max()
+      .unwrap_or_default();
+
+    for key in Self::KEYS.iter().copied().chain(vaults) {
+      match self.sources.get(key) {
+        Some((value, source)) => writeln!(
+          out,
+          "{:width$} = {}  # {}",
+          key,
+          value,
+          source,
+          width = width
+        )?,
+        None => writeln!(out, "{:width$}   # not set", key, width = width)?,
+      }
+    }
+
+    for key in unknown {
+      let (value, source) = &self.sources[key];
+
+      writeln!(
+        out,
+        "{:width$} = {}  # unknown key, from {}",
+        key,
+        value,
+        source,
+        width = width
+      )?;
+    }
+
+    Ok(())
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  fn layered(
+    files: &[(&str, &str)],
+    vars: &[(&str, &str)],
+    flags: &[(&'static str, &str, &str)],
+  ) -> Config {
+    let root = env::current_dir().unwrap();
+
+    for (path, content) in files {
+      let path = root.join(path);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, content).unwrap();
+    }
+
+    fs::create_dir_all(root.join("home/vault/notes")).unwrap();
+
+    let vars = vars
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect::<BTreeMap<_, _>>();
+
+    Config::layered(
+      Some(root.join("home/.zk.toml")).filter(|path| path.exists()),
+      &root.join("home/vault/notes"),
+      |name| vars.get(name).cloned(),
+      &flags
+        .iter()
+        .map(|(flag, key, value)| (*flag, *key, value.to_string()))
+        .collect::<Vec<_>>(),
+    )
+    .unwrap()
+  }
+
   #[test]
-  fn default() -> Result<()> {
-    let config: Config = toml::from_str(Config::default())?;
-    assert_eq!(config.path.to_str().unwrap(), "~/.zk");
-    assert_eq!(config.editor, "vim");
-    Ok(())
+  fn defaults() {
+    in_temp_dir!({
+      let config = layered(&[], &[], &[]);
+
+      assert_eq!(
+        Config {
+          sources: BTreeMap::new(),
+          ..config.clone()
+        },
+        Config::default()
+      );
+
+      assert_eq!(config.sources["path"].1, Source::Default);
+      assert!(!config.sources.contains_key("journal.daily_template"));
+    });
+  }
+
+  #[test]
+  fn precedence() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let config = layered(
+        &[
+          (
+            "home/.zk.toml",
+            indoc! {"
+              path      = '~/notes'
+              editor    = 'nvim'
+              id_scheme = 'ulid'
+
+              [journal]
+              daily = 'journal-%Y%m%d'
+            "},
+          ),
+          (
+            "home/vault/.zk.toml",
+            indoc! {"
+              id_scheme = 'millis'
+              templates = 'templates'
+            "},
+          ),
+        ],
+        &[("EDITOR", "code --wait"), ("ZK_JOURNAL_WEEKLY", "%Y-W%W")],
+        &[],
+      );
+
+      assert_eq!(config.path, root.join("home/vault"));
+      assert_eq!(config.editor, "nvim");
+      assert_eq!(config.id_scheme, IdScheme::Millis);
+      assert_eq!(config.templates, root.join("home/vault/templates"));
+      assert_eq!(config.journal.daily, "journal-%Y%m%d");
+      assert_eq!(config.journal.weekly, "%Y-W%W");
+
+      assert_eq!(
+        config.sources["path"].1,
+        Source::Project(root.join("home/vault/.zk.toml"))
+      );
+      assert_eq!(
+        config.sources["journal.daily"].1,
+        Source::User(root.join("home/.zk.toml"))
+      );
+      assert_eq!(
+        config.sources["journal.weekly"].1,
+        Source::Environment("ZK_JOURNAL_WEEKLY".into())
+      );
+    });
+  }
+
+  #[test]
+  fn editor_variables_and_flags() {
+    in_temp_dir!({
+      let config = layered(
+        &[],
+        &[("EDITOR", "code --wait"), ("ZK_PATH", "/env")],
+        &[("--dir", "path", "/flag")],
+      );
+
+      assert_eq!(config.editor, "code --wait");
+      assert_eq!(config.sources["editor"].1, Source::Editor("EDITOR".into()));
+      assert_eq!(config.path, PathBuf::from("/flag"));
+      assert_eq!(config.sources["path"].1, Source::Flag("--dir"));
+    });
+  }
+
+  #[test]
+  fn show() {
+    in_temp_dir!({
+      let config = layered(
+        &[(
+          "home/.zk.toml",
+          indoc! {"
+            path   = '/zk'
+            editr  = 'nvim'
+          "},
+        )],
+        &[("ZK_EDITOR", "vi")],
+        &[],
+      );
+
+      let mut out = Vec::new();
+      config.show(&mut out).unwrap();
+
+      let out = String::from_utf8(out).unwrap();
+      let root = env::current_dir().unwrap();
+
+      assert!(out.contains(&format!(
+        "path                    = \"/zk\"  # user config {}\n",
+        root.join("home/.zk.toml").display()
+      )));
+      assert!(out.contains("editor                  = \"vi\"  # $ZK_EDITOR\n"));
+      assert!(out.contains("journal.daily_template    # not set\n"));
+      assert!(out.contains("editr                   = \"nvim\"  # unknown key"));
+    });
+  }
+
+  #[test]
+  fn vaults() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let user = (
+        "home/.zk.toml",
+        indoc! {"
+          path  = '/notes'
+          vault = 'personal'
+
+          [vaults]
+          personal = '/personal'
+          work     = '/work'
+        "},
+      );
+
+      let config = layered(&[user], &[], &[]);
+
+      assert_eq!(config.vault_path().unwrap(), PathBuf::from("/personal"));
+
+      let config = layered(&[user], &[], &[("--vault", "vault", "work")]);
+
+      assert_eq!(config.vault_path().unwrap(), PathBuf::from("/work"));
+
+      let config = layered(&[user], &[], &[("--dir", "path", "/dir")]);
+
+      assert_eq!(config.vault_path().unwrap(), PathBuf::from("/dir"));
+
+      let config = layered(&[user], &[], &[("--vault", "vault", "play")]);
+
+      assert!(matches!(
+        config.vault_path(),
+        Err(Error::VaultNotFound { .. })
+      ));
+
+      let config = layered(
+        &[
+          user,
+          (
+            "home/vault/.zk.toml",
+            indoc! {"
+              [vaults]
+              shared = 'shared'
+            "},
+          ),
+        ],
+        &[],
+        &[],
+      );
+
+      assert_eq!(config.vault_path().unwrap(), root.join("home/vault"));
+      assert_eq!(config.vaults["shared"], root.join("home/vault/shared"));
+      assert_eq!(config.vaults["work"], PathBuf::from("/work"));
+    });
+  }
+
+  #[test]
+  fn invalid_file() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::write(root.join(".zk.toml"), "path = ").unwrap();
+
+      assert!(matches!(
+        Config::layered(None, &root, |_| None, &[]),
+        Err(Error::ConfigInvalid { .. })
+      ));
+    });
+  }
+
+  #[test]
+  fn editor() {
+    let config = Config {
+      editor: "code --wait".into(),
+      ..Config::default()
+    };
+
+    assert_eq!(
+      config.editor().unwrap(),
+      Editor::parse("code --wait").unwrap()
+    );
   }
 }
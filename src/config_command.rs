@@ -0,0 +1,16 @@
+use crate::common::*;
+
+#[derive(Debug, Parser)]
+pub(crate) enum ConfigCommand {
+  #[clap(name = "show")]
+  /// Print every setting with its value and where it came from
+  Show,
+}
+
+impl ConfigCommand {
+  pub(crate) fn run(self, handler: &Handler) -> Result<()> {
+    match self {
+      Self::Show => Ok(handler.config.show(&mut io::stdout().lock())?),
+    }
+  }
+}
@@ -1,25 +1,122 @@
-use crate::common::*;
+use {crate::common::*, std::cell::RefCell};
 
 #[derive(Debug)]
 pub(crate) struct Directory {
   pub(crate) path: PathBuf,
+  /// The other vaults cross-vault links can refer to, by name
+  pub(crate) vaults: BTreeMap<String, PathBuf>,
+  /// The notes as last read, along with the `store` generation they were
+  /// read at, so that a command reads the vault again only after changing it
+  cache: RefCell<Option<(usize, Vec<Note>)>>,
 }
 
 impl Directory {
   pub(crate) fn new(path: PathBuf) -> Self {
-    Self { path }
+    Self {
+      path,
+      vaults: BTreeMap::new(),
+      cache: RefCell::new(None),
+    }
   }
 
-  pub fn notes(&self) -> Result<Vec<Note>> {
-    WalkDir::new(&self.path)
-      .into_iter()
-      .collect::<Result<Vec<_>, _>>()?
+  pub(crate) fn with_vaults(self, vaults: BTreeMap<String, PathBuf>) -> Self {
+    Self { vaults, ..self }
+  }
+
+  /// The vault a cross-vault link refers to and the link within it, if the
+  /// vault is configured.
+  pub(crate) fn remote(&self, link: &Link) -> Option<(Directory, Link)> {
+    let (name, link) = link.remote()?;
+    let path = self.vaults.get(name)?;
+    Some((Directory::new(path.clone()), link))
+  }
+
+  /// The vault-local directory `zk` keeps its own state in.
+  pub(crate) fn data_dir(&self) -> PathBuf {
+    self.path.join(".zk")
+  }
+
+  /// Every file in the vault, skipping hidden files and directories such as
+  /// the data directory or a `.git` checkout.
+  pub(crate) fn files(&self) -> Result<Vec<PathBuf>> {
+    Ok(
+      WalkDir::new(&self.path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+          entry.depth() == 0
+            || !entry
+              .file_name()
+              .to_str()
+              .is_some_and(|name| name.starts_with('.'))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|entry| entry.into_path())
+        .filter(|entry| entry.is_file())
+        .collect(),
+    )
+  }
+
+  pub(crate) fn paths(&self) -> Result<Vec<PathBuf>> {
+    Ok(
+      self
+        .files()?
+        .into_iter()
+        .filter(|entry| entry.unwrapped_extension() == "md")
+        .collect(),
+    )
+  }
+
+  /// An id for a new note called `name`, with a prefix no other file in the
+  /// vault uses.
+  pub(crate) fn new_id(&self, name: &str, scheme: IdScheme) -> Result<NoteId> {
+    let taken = self
+      .files()?
       .iter()
-      .cloned()
-      .map(|entry| entry.into_path())
-      .filter(|entry| entry.is_file() && entry.unwrapped_extension() == "md")
-      .map(Note::from)
-      .collect::<Result<Vec<_>, _>>()
+      .filter_map(|path| NoteId::parse(path.unwrapped_filename()))
+      .map(|id| id.prefix)
+      .collect::<BTreeSet<_>>();
+
+    Ok(NoteId::new(
+      &scheme.generate(Utc::now(), |prefix| taken.contains(prefix)),
+      name,
+    ))
+  }
+
+  /// The notes of another vault. A configured vault whose directory is
+  /// missing has none, so links into it read as broken instead of failing
+  /// the command. Its index is only read, never written.
+  pub(crate) fn remote_notes(&self) -> Result<Vec<Note>> {
+    if !self.path.is_dir() {
+      return Ok(Vec::new());
+    }
+
+    Index::load(&self.data_dir()).refresh(&self.path, self.paths()?)
+  }
+
+  pub fn notes(&self) -> Result<Vec<Note>> {
+    let generation = store::generation();
+
+    if let Some((read, notes)) = &*self.cache.borrow() {
+      if *read == generation {
+        return Ok(notes.clone());
+      }
+    }
+
+    let mut index = Index::load(&self.data_dir());
+    let notes = index.refresh(&self.path, self.paths()?)?;
+    index.save(&self.data_dir())?;
+
+    *self.cache.borrow_mut() = Some((generation, notes.clone()));
+
+    Ok(notes)
+  }
+
+  /// The names of the notes in the vault, which decide what name-only links
+  /// resolve to.
+  pub(crate) fn names(&self) -> Result<Names> {
+    Ok(Names::new(self.notes()?.iter().map(|note| &note.id)))
   }
 
   pub(crate) fn find(&self, name: &str) -> Result<Vec<Note>> {
@@ -39,28 +136,115 @@ impl Directory {
     Ok(notes.to_vec())
   }
 
-  pub(crate) fn find_by_tag(&self, tag: &str) -> Result<Vec<Note>> {
-    let notes = &self
-      .notes()?
-      .iter()
-      .filter(|note| {
-        note
-          .matter
-          .tags
-          .to_owned()
-          .unwrap_or_default()
-          .contains(&tag.to_string())
+  /// The notes `note` links to, through its frontmatter or its body.
+  pub(crate) fn links_of(&self, note: &Note) -> Result<Vec<Note>> {
+    let links = note.links();
+
+    let notes = self.notes()?;
+    let names = Names::new(notes.iter().map(|note| &note.id));
+
+    let mut notes = notes
+      .into_iter()
+      .filter(|other| {
+        other.path != note.path
+          && links.iter().any(|link| link.points_to(&other.id, &names))
       })
-      .cloned()
+      .collect::<Vec<Note>>();
+
+    for (vault, link) in links.iter().filter_map(|link| self.remote(link)) {
+      let others = vault.remote_notes()?;
+      let names = Names::new(others.iter().map(|note| &note.id));
+
+      notes.extend(
+        others
+          .into_iter()
+          .filter(|other| link.points_to(&other.id, &names)),
+      );
+    }
+
+    Ok(notes)
+  }
+
+  /// Every resolved link between `notes`, as pairs of indices of the linking
+  /// and the linked note.
+  pub(crate) fn edges(notes: &[Note]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+
+    let names = Names::new(notes.iter().map(|note| &note.id));
+
+    for (from, note) in notes.iter().enumerate() {
+      let links = note.links();
+
+      for (to, other) in notes.iter().enumerate() {
+        if from != to
+          && links.iter().any(|link| link.points_to(&other.id, &names))
+        {
+          edges.push((from, to));
+        }
+      }
+    }
+
+    edges
+  }
+
+  /// The inverse link graph, mapping each linked note id to the notes that
+  /// link to it.
+  pub(crate) fn backlinks(&self) -> Result<BTreeMap<String, Vec<Note>>> {
+    let notes = self.notes()?;
+
+    let mut graph = BTreeMap::<String, Vec<Note>>::new();
+
+    for (from, to) in Self::edges(&notes) {
+      graph
+        .entry(notes[to].id.to_string())
+        .or_default()
+        .push(notes[from].clone());
+    }
+
+    Ok(graph)
+  }
+
+  /// The notes linking to `note`, including notes in other vaults that
+  /// reach it through a cross-vault link.
+  pub(crate) fn backlinks_of(&self, note: &Note) -> Result<Vec<Note>> {
+    let mut backlinks = self
+      .backlinks()?
+      .remove(&note.id.to_string())
+      .unwrap_or_default();
+
+    let names = self.names()?;
+
+    let paths = self.vaults.values().collect::<BTreeSet<_>>();
+
+    for path in paths.into_iter().filter(|path| **path != self.path) {
+      let vault = Directory::new(path.clone()).with_vaults(self.vaults.clone());
+
+      backlinks.extend(vault.remote_notes()?.into_iter().filter(|other| {
+        other.links().iter().any(|link| {
+          vault.remote(link).is_some_and(|(target, link)| {
+            target.path == self.path && link.points_to(&note.id, &names)
+          })
+        })
+      }));
+    }
+
+    Ok(backlinks)
+  }
+
+  pub(crate) fn find_by_query(&self, query: &Query) -> Result<Vec<Note>> {
+    let notes = self
+      .notes()?
+      .into_iter()
+      .filter(|note| query.matches(note))
       .collect::<Vec<Note>>();
 
     if notes.is_empty() {
       return Err(Error::TagNotFound {
-        tag: tag.to_owned(),
+        tag: query.to_string(),
       });
     }
 
-    Ok(notes.to_vec())
+    Ok(notes)
   }
 }
 
@@ -85,12 +269,73 @@ mod tests {
     });
   }
 
+  #[test]
+  fn notes_skips_data_dir() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+
+      fs::create_dir_all(directory.data_dir()).unwrap();
+      fs::write(directory.data_dir().join("1625104269-b.md"), "").unwrap();
+
+      assert_eq!(directory.notes().unwrap().len(), 1);
+    });
+  }
+
+  #[test]
+  fn notes_writes_index() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+
+      assert_eq!(directory.notes().unwrap().len(), 1);
+      assert!(directory.data_dir().join("index.json").exists());
+
+      create_note("b").unwrap();
+
+      assert_eq!(directory.notes().unwrap().len(), 2);
+    });
+  }
+
+  #[test]
+  fn notes_read_once_until_changed() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+      let index = directory.data_dir().join("index.json");
+
+      directory.notes().unwrap();
+      fs::remove_file(&index).unwrap();
+
+      assert_eq!(directory.notes().unwrap().len(), 1);
+      assert!(!index.exists());
+
+      create_note("b").unwrap();
+
+      assert_eq!(directory.notes().unwrap().len(), 2);
+      assert!(index.exists());
+
+      let modified = fs::metadata(&index).unwrap().modified().unwrap();
+
+      assert_eq!(
+        Directory::new(directory.path.clone())
+          .notes()
+          .unwrap()
+          .len(),
+        2
+      );
+      assert_eq!(fs::metadata(&index).unwrap().modified().unwrap(), modified);
+    });
+  }
+
   #[test]
   fn find() {
     in_temp_dir!({
       for _ in 0..5 {
         create_note("a").unwrap();
-        sleep();
       }
 
       let notes = Directory::new(env::current_dir().unwrap())
@@ -105,6 +350,192 @@ mod tests {
     });
   }
 
+  #[test]
+  fn backlinks() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+      let mut b = create_note("b").unwrap();
+      let c = create_note("c").unwrap();
+
+      a.add_link(&c.id.to_string(), &Names::default()).unwrap();
+      b.add_link(&c.id.to_string(), &Names::default()).unwrap();
+      b.add_link(&a.id.to_string(), &Names::default()).unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+
+      let mut backlinks = directory
+        .backlinks_of(&c)
+        .unwrap()
+        .into_iter()
+        .map(|note| note.id.name)
+        .collect::<Vec<_>>();
+
+      backlinks.sort();
+
+      assert_eq!(backlinks, vec!["a", "b"]);
+
+      assert_eq!(directory.backlinks_of(&a).unwrap().len(), 1);
+      assert!(directory.backlinks_of(&b).unwrap().is_empty());
+    });
+  }
+
+  #[test]
+  fn cross_vault_links() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir_all(root.join("personal")).unwrap();
+      fs::create_dir_all(root.join("work")).unwrap();
+
+      fs::write(
+        root.join("personal/1625104269-a.md"),
+        indoc! {"
+          ---
+          name: a
+          links:
+            - work:1625104326-b.md
+          ---
+          See [[work:c]] and [[play:d]].
+        "},
+      )
+      .unwrap();
+
+      fs::write(root.join("work/1625104326-b.md"), "---\nname: b\n---\n")
+        .unwrap();
+      fs::write(root.join("work/1625104359-c.md"), "---\nname: c\n---\n")
+        .unwrap();
+      fs::write(root.join("work/1625104399-d.md"), "---\nname: d\n---\n")
+        .unwrap();
+
+      let directory = Directory::new(root.join("personal")).with_vaults(
+        vec![("work".to_owned(), root.join("work"))]
+          .into_iter()
+          .collect(),
+      );
+
+      let note = Note::from(root.join("personal/1625104269-a.md")).unwrap();
+
+      assert_eq!(
+        directory
+          .links_of(&note)
+          .unwrap()
+          .into_iter()
+          .map(|note| note.path)
+          .collect::<Vec<_>>(),
+        vec![
+          root.join("work/1625104326-b.md"),
+          root.join("work/1625104359-c.md")
+        ]
+      );
+
+      assert!(!root.join("work/.zk").exists());
+    });
+  }
+
+  #[test]
+  fn cross_vault_backlinks() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir_all(root.join("personal")).unwrap();
+      fs::create_dir_all(root.join("work")).unwrap();
+
+      fs::write(
+        root.join("personal/1625104269-a.md"),
+        "---\nname: a\n---\nSee [[work:b]].\n",
+      )
+      .unwrap();
+      fs::write(root.join("work/1625104326-b.md"), "---\nname: b\n---\n")
+        .unwrap();
+      fs::write(
+        root.join("work/1625104359-c.md"),
+        "---\nname: c\n---\nSee [[b]].\n",
+      )
+      .unwrap();
+
+      let vaults = vec![
+        ("personal".to_owned(), root.join("personal")),
+        ("work".to_owned(), root.join("work")),
+      ]
+      .into_iter()
+      .collect::<BTreeMap<_, _>>();
+
+      let directory = Directory::new(root.join("work")).with_vaults(vaults);
+
+      let note = Note::from(root.join("work/1625104326-b.md")).unwrap();
+
+      let mut backlinks = directory
+        .backlinks_of(&note)
+        .unwrap()
+        .into_iter()
+        .map(|note| note.path)
+        .collect::<Vec<_>>();
+
+      backlinks.sort();
+
+      assert_eq!(
+        backlinks,
+        vec![
+          root.join("personal/1625104269-a.md"),
+          root.join("work/1625104359-c.md")
+        ]
+      );
+    });
+  }
+
+  #[test]
+  fn missing_vault() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::write(
+        root.join("1625104269-a.md"),
+        "---\nname: a\n---\nSee [[work:b]].\n",
+      )
+      .unwrap();
+
+      let directory = Directory::new(root.clone()).with_vaults(
+        vec![("work".to_owned(), root.join("missing"))]
+          .into_iter()
+          .collect(),
+      );
+
+      let note = Note::from(root.join("1625104269-a.md")).unwrap();
+
+      assert!(directory.links_of(&note).unwrap().is_empty());
+      assert!(directory.backlinks_of(&note).unwrap().is_empty());
+      assert!(!root.join("missing").exists());
+    });
+  }
+
+  #[test]
+  fn inline_links() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+      let b = create_note("b").unwrap();
+      let c = create_note("c").unwrap();
+
+      a.content = format!("See [[b]] and [c]({}).\n", c.id);
+      a.add_tag("software").unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+
+      let mut links = directory
+        .links_of(&a)
+        .unwrap()
+        .into_iter()
+        .map(|note| note.id.name)
+        .collect::<Vec<_>>();
+
+      links.sort();
+
+      assert_eq!(links, vec!["b", "c"]);
+
+      assert_eq!(directory.backlinks_of(&b).unwrap()[0].id.name, "a");
+      assert_eq!(directory.backlinks_of(&c).unwrap()[0].id.name, "a");
+    });
+  }
+
   #[test]
   fn find_by_tag() {
     in_temp_dir!({
@@ -115,7 +546,7 @@ mod tests {
       b.add_tag("software").unwrap();
 
       let notes = Directory::new(env::current_dir().unwrap())
-        .find_by_tag("software")
+        .find_by_query(&Query::parse("software").unwrap())
         .unwrap();
 
       assert_eq!(notes.len(), 2);
@@ -125,4 +556,30 @@ mod tests {
       }
     });
   }
+
+  #[test]
+  fn find_by_query() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+      let mut b = create_note("b").unwrap();
+      create_note("c").unwrap();
+
+      a.add_tag("software").unwrap();
+      b.add_tag("software").unwrap();
+      b.add_tag("archived").unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+
+      let notes = directory
+        .find_by_query(&Query::parse("software and not archived").unwrap())
+        .unwrap();
+
+      assert_eq!(notes.len(), 1);
+      assert_eq!(notes[0].id.name, "a");
+
+      assert!(directory
+        .find_by_query(&Query::parse("missing").unwrap())
+        .is_err());
+    });
+  }
 }
@@ -0,0 +1,514 @@
+use crate::common::*;
+
+/// A consistency problem found in the vault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Problem {
+  /// A wikilink giving only a name that several notes have
+  AmbiguousLink {
+    path: PathBuf,
+    target: String,
+    count: usize,
+  },
+  /// A link whose target does not exist
+  BrokenLink {
+    path: PathBuf,
+    target: String,
+    inline: bool,
+  },
+  /// Several notes sharing the same id prefix
+  DuplicateId { prefix: String, paths: Vec<PathBuf> },
+  /// A `sequence` value that isn't a Folgezettel sequence
+  InvalidSequence { path: PathBuf, value: String },
+  /// A `created` or `updated` value that isn't a timestamp
+  InvalidTimestamp {
+    path: PathBuf,
+    key: String,
+    value: String,
+  },
+  /// Frontmatter that cannot be parsed
+  MalformedFrontmatter { path: PathBuf, message: String },
+  /// A frontmatter `name` that differs from the name in the filename
+  NameMismatch { path: PathBuf, name: String },
+  /// A file that is not a markdown note named `<prefix>-<name>.md`
+  NonNote { path: PathBuf },
+  /// A frontmatter link that is not linked back
+  OneWayLink { path: PathBuf, target: PathBuf },
+}
+
+impl Display for Problem {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Problem::AmbiguousLink {
+        path,
+        target,
+        count,
+      } => write!(
+        f,
+        "{}: link `[[{}]]` is ambiguous, {} notes have that name",
+        path.display(),
+        target,
+        count
+      ),
+      Problem::BrokenLink {
+        path,
+        target,
+        inline,
+      } => write!(
+        f,
+        "{}: broken {} link to `{}`",
+        path.display(),
+        if *inline { "inline" } else { "frontmatter" },
+        target
+      ),
+      Problem::DuplicateId { prefix, paths } => write!(
+        f,
+        "duplicate id prefix `{}`: {}",
+        prefix,
+        paths
+          .iter()
+          .map(|path| path.display().to_string())
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+      Problem::InvalidSequence { path, value } => write!(
+        f,
+        "{}: `sequence` is not a sequence: `{}`",
+        path.display(),
+        value
+      ),
+      Problem::InvalidTimestamp { path, key, value } => write!(
+        f,
+        "{}: `{}` is not a timestamp: `{}`",
+        path.display(),
+        key,
+        value
+      ),
+      Problem::MalformedFrontmatter { path, message } => {
+        write!(f, "{}: malformed frontmatter: {}", path.display(), message)
+      }
+      Problem::NameMismatch { path, name } => write!(
+        f,
+        "{}: frontmatter name `{}` does not match the filename",
+        path.display(),
+        name
+      ),
+      Problem::NonNote { path } => {
+        write!(f, "{}: not a note", path.display())
+      }
+      Problem::OneWayLink { path, target } => write!(
+        f,
+        "{}: links to `{}`, which does not link back",
+        path.display(),
+        target.unwrapped_filename()
+      ),
+    }
+  }
+}
+
+impl Problem {
+  /// Scan every file in `directory` for problems, without failing on notes
+  /// that cannot be read. Also returns the names of the notes that could be
+  /// read, which fixing the problems resolves links against.
+  pub(crate) fn diagnose(directory: &Directory) -> Result<(Vec<Self>, Names)> {
+    let mut problems = Vec::new();
+    let mut notes = Vec::new();
+
+    for path in directory.files()? {
+      let id = NoteId::parse(path.unwrapped_filename());
+
+      let is_note = path.unwrapped_extension() == "md"
+        && id.is_some_and(|id| !id.prefix.is_empty() && !id.name.is_empty());
+
+      if !is_note {
+        problems.push(Problem::NonNote { path });
+        continue;
+      }
+
+      match Note::from(path.clone()) {
+        Ok(note) => notes.push(note),
+        Err(error) => problems.push(Problem::MalformedFrontmatter {
+          path,
+          message: error.to_string(),
+        }),
+      }
+    }
+
+    let names = Names::new(notes.iter().map(|note| &note.id));
+
+    let mut prefixes = BTreeMap::<&str, Vec<PathBuf>>::new();
+
+    for note in &notes {
+      prefixes
+        .entry(&note.id.prefix)
+        .or_default()
+        .push(note.path.clone());
+    }
+
+    for (prefix, paths) in prefixes {
+      if paths.len() > 1 {
+        problems.push(Problem::DuplicateId {
+          prefix: prefix.to_owned(),
+          paths,
+        });
+      }
+    }
+
+    for note in &notes {
+      for (key, value) in note.matter.invalid_timestamps() {
+        problems.push(Problem::InvalidTimestamp {
+          path: note.path.clone(),
+          key,
+          value,
+        });
+      }
+
+      if let Some(value) = note.matter.invalid_sequence() {
+        problems.push(Problem::InvalidSequence {
+          path: note.path.clone(),
+          value,
+        });
+      }
+
+      if note.matter.name != note.id.name {
+        problems.push(Problem::NameMismatch {
+          path: note.path.clone(),
+          name: note.matter.name.clone(),
+        });
+      }
+
+      for link in note.links() {
+        if let Some((vault, remote)) = directory.remote(&link) {
+          let others = vault.remote_notes()?;
+          let names = Names::new(others.iter().map(|other| &other.id));
+
+          let resolved = others
+            .iter()
+            .any(|other| remote.points_to(&other.id, &names));
+
+          if !resolved {
+            problems.push(Problem::BrokenLink {
+              path: note.path.clone(),
+              target: link.target.clone(),
+              inline: link.kind != LinkKind::Matter,
+            });
+          }
+
+          continue;
+        }
+
+        let targets = notes
+          .iter()
+          .filter(|other| link.points_to(&other.id, &names))
+          .collect::<Vec<_>>();
+
+        let name = link.target.strip_suffix(".md").unwrap_or(&link.target);
+
+        if targets.is_empty()
+          && link.kind == LinkKind::Wiki
+          && names.count(name) > 1
+        {
+          problems.push(Problem::AmbiguousLink {
+            path: note.path.clone(),
+            target: name.to_owned(),
+            count: names.count(name),
+          });
+          continue;
+        }
+
+        if targets.is_empty() {
+          problems.push(Problem::BrokenLink {
+            path: note.path.clone(),
+            target: link.target.clone(),
+            inline: link.kind != LinkKind::Matter,
+          });
+          continue;
+        }
+
+        if link.kind == LinkKind::Matter {
+          for target in targets {
+            if !target.has_link(&note.id.to_string(), &names) {
+              problems.push(Problem::OneWayLink {
+                path: note.path.clone(),
+                target: target.path.clone(),
+              });
+            }
+          }
+        }
+      }
+    }
+
+    Ok((problems, names))
+  }
+
+  /// Whether `fix` can repair this problem.
+  pub(crate) fn is_fixable(&self) -> bool {
+    matches!(
+      self,
+      Problem::BrokenLink { inline: false, .. }
+        | Problem::NameMismatch { .. }
+        | Problem::OneWayLink { .. }
+    )
+  }
+
+  /// Repair the problem in a vault with the note `names`: drop broken
+  /// frontmatter links, add the missing side of one-way links and rename
+  /// frontmatter to match the filename.
+  pub(crate) fn fix(&self, names: &Names) -> Result<()> {
+    match self {
+      Problem::BrokenLink {
+        path,
+        target,
+        inline: false,
+      } => {
+        Note::from(path.clone())?.remove_link(target, names)?;
+      }
+      Problem::NameMismatch { path, .. } => {
+        Note::from(path.clone())?.set_name()?;
+      }
+      Problem::OneWayLink { path, target } => {
+        Note::from(target.clone())?
+          .add_link(path.unwrapped_filename(), names)?;
+      }
+      _ => {}
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write(path: &Path, content: &str) {
+    fs::write(path, content).unwrap();
+  }
+
+  #[test]
+  fn diagnose() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(
+        &root.join("1625104269-a.md"),
+        indoc! {"
+          ---
+          name: a
+          links:
+            - 1625104326-b.md
+            - 1625104999-x.md
+          ---
+          See [[missing]] and [[b]].
+        "},
+      );
+
+      write(
+        &root.join("1625104326-b.md"),
+        indoc! {"
+          ---
+          name: bee
+          created: last tuesday
+          sequence: intro
+          ---
+        "},
+      );
+
+      write(&root.join("1625104326-c.md"), "---\nname: [\n---\n");
+      write(&root.join("notes.txt"), "");
+      write(&root.join("readme.md"), "");
+
+      fs::create_dir(root.join(".git")).unwrap();
+      write(&root.join(".git").join("HEAD"), "");
+
+      let problems =
+        Problem::diagnose(&Directory::new(root.clone())).unwrap().0;
+
+      let a = root.join("1625104269-a.md");
+      let b = root.join("1625104326-b.md");
+
+      assert_eq!(problems.len(), 9, "{:#?}", problems);
+
+      for problem in [
+        Problem::NonNote {
+          path: root.join("notes.txt"),
+        },
+        Problem::NonNote {
+          path: root.join("readme.md"),
+        },
+        Problem::NameMismatch {
+          path: b.clone(),
+          name: "bee".into(),
+        },
+        Problem::InvalidTimestamp {
+          path: b.clone(),
+          key: "created".into(),
+          value: "last tuesday".into(),
+        },
+        Problem::InvalidSequence {
+          path: b.clone(),
+          value: "intro".into(),
+        },
+        Problem::BrokenLink {
+          path: a.clone(),
+          target: "1625104999-x.md".into(),
+          inline: false,
+        },
+        Problem::BrokenLink {
+          path: a.clone(),
+          target: "missing".into(),
+          inline: true,
+        },
+        Problem::OneWayLink {
+          path: a.clone(),
+          target: b.clone(),
+        },
+      ] {
+        assert!(problems.contains(&problem), "{}", problem);
+      }
+
+      assert!(problems.iter().any(|problem| matches!(
+        problem,
+        Problem::MalformedFrontmatter { .. }
+      )));
+    });
+  }
+
+  #[test]
+  fn cross_vault_links() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir_all(root.join("personal")).unwrap();
+      fs::create_dir_all(root.join("work")).unwrap();
+
+      write(
+        &root.join("personal/1625104269-a.md"),
+        indoc! {"
+          ---
+          name: a
+          links:
+            - work:1625104326-b.md
+            - work:1625104999-x.md
+          ---
+        "},
+      );
+      write(&root.join("work/1625104326-b.md"), "---\nname: b\n---\n");
+
+      let directory = Directory::new(root.join("personal")).with_vaults(
+        vec![("work".to_owned(), root.join("work"))]
+          .into_iter()
+          .collect(),
+      );
+
+      assert_eq!(
+        Problem::diagnose(&directory).unwrap().0,
+        vec![Problem::BrokenLink {
+          path: root.join("personal/1625104269-a.md"),
+          target: "work:1625104999-x.md".into(),
+          inline: false,
+        }]
+      );
+    });
+  }
+
+  #[test]
+  fn missing_vault() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(
+        &root.join("1625104269-a.md"),
+        "---\nname: a\n---\nSee [[work:b]].\n",
+      );
+
+      let directory = Directory::new(root.clone()).with_vaults(
+        vec![("work".to_owned(), root.join("missing"))]
+          .into_iter()
+          .collect(),
+      );
+
+      assert_eq!(
+        Problem::diagnose(&directory).unwrap().0,
+        vec![Problem::BrokenLink {
+          path: root.join("1625104269-a.md"),
+          target: "work:b".into(),
+          inline: true,
+        }]
+      );
+    });
+  }
+
+  #[test]
+  fn ambiguous_link() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(&root.join("1625104269-a.md"), "---\nname: a\n---\n");
+      write(&root.join("1625104326-a.md"), "---\nname: a\n---\n");
+      write(
+        &root.join("1625104344-c.md"),
+        "---\nname: c\n---\nSee [[a]] and [[1625104326-a]].\n",
+      );
+
+      let problem = Problem::AmbiguousLink {
+        path: root.join("1625104344-c.md"),
+        target: "a".into(),
+        count: 2,
+      };
+
+      assert_eq!(
+        Problem::diagnose(&Directory::new(root.clone())).unwrap().0,
+        vec![problem.clone()]
+      );
+
+      assert!(!problem.is_fixable());
+    });
+  }
+
+  #[test]
+  fn duplicate_id() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(&root.join("1625104269-a.md"), "---\nname: a\n---\n");
+      write(&root.join("1625104269-b.md"), "---\nname: b\n---\n");
+
+      assert_eq!(
+        Problem::diagnose(&Directory::new(root.clone())).unwrap().0,
+        vec![Problem::DuplicateId {
+          prefix: "1625104269".into(),
+          paths: vec![
+            root.join("1625104269-a.md"),
+            root.join("1625104269-b.md")
+          ],
+        }]
+      );
+    });
+  }
+
+  #[test]
+  fn fix() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(
+        &root.join("1625104269-a.md"),
+        "---\nname: a\nlinks:\n  - 1625104326-b.md\n  - 1625104999-x.md\n---\n",
+      );
+      write(&root.join("1625104326-b.md"), "---\nname: bee\n---\n");
+
+      let directory = Directory::new(root.clone());
+
+      let problems = Problem::diagnose(&directory).unwrap().0;
+
+      assert_eq!(problems.len(), 3);
+
+      for problem in &problems {
+        assert!(problem.is_fixable());
+        problem.fix(&directory.names().unwrap()).unwrap();
+      }
+
+      assert!(Problem::diagnose(&directory).unwrap().0.is_empty());
+    });
+  }
+}
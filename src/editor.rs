@@ -0,0 +1,168 @@
+use crate::common::*;
+
+/// A position in a file to open the editor at, such as a full-text search
+/// hit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Location {
+  pub(crate) path: PathBuf,
+  pub(crate) line: usize,
+  pub(crate) col: usize,
+}
+
+impl Location {
+  pub(crate) fn new(path: &Path) -> Self {
+    Self::at(path, 1, 1)
+  }
+
+  pub(crate) fn at(path: &Path, line: usize, col: usize) -> Self {
+    Self {
+      path: path.to_owned(),
+      line,
+      col,
+    }
+  }
+}
+
+/// An editor command line, such as `code --wait` or `nvim +{line} {path}`.
+///
+/// The words mentioning `{path}`, `{line}` or `{col}` are repeated for every
+/// file opened, so several notes open in a single invocation. Without any
+/// placeholders the paths are appended at the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Editor {
+  words: Vec<String>,
+}
+
+impl Editor {
+  const PLACEHOLDERS: &'static [&'static str] = &["{path}", "{line}", "{col}"];
+
+  pub(crate) fn parse(command: &str) -> Result<Self> {
+    let words =
+      shell_words::split(command).map_err(|_| Error::EditorInvalid {
+        command: command.to_owned(),
+      })?;
+
+    if words.is_empty() {
+      return Err(Error::EditorInvalid {
+        command: command.to_owned(),
+      });
+    }
+
+    Ok(Self { words })
+  }
+
+  /// The program and arguments that open every one of `locations`.
+  pub(crate) fn args(&self, locations: &[Location]) -> Vec<String> {
+    let templated = |word: &String| {
+      Self::PLACEHOLDERS
+        .iter()
+        .any(|placeholder| word.contains(placeholder))
+    };
+
+    let (start, end) = match (
+      self.words.iter().position(templated),
+      self.words.iter().rposition(templated),
+    ) {
+      (Some(start), Some(end)) => (start, end + 1),
+      _ => (self.words.len(), self.words.len()),
+    };
+
+    let group = if start == end {
+      vec!["{path}".to_owned()]
+    } else {
+      self.words[start..end].to_vec()
+    };
+
+    let mut args = self.words[..start].to_vec();
+
+    for location in locations {
+      args.extend(group.iter().map(|word| {
+        word
+          .replace("{path}", &location.path.display().to_string())
+          .replace("{line}", &location.line.to_string())
+          .replace("{col}", &location.col.to_string())
+      }));
+    }
+
+    args.extend(self.words[end..].iter().cloned());
+
+    args
+  }
+
+  /// Open `locations` in a single editor invocation, waiting for it to exit.
+  pub(crate) fn open(&self, locations: &[Location]) -> Result<()> {
+    if locations.is_empty() {
+      return Ok(());
+    }
+
+    let args = self.args(locations);
+
+    let status = Command::new(&args[0]).args(&args[1..]).status()?;
+
+    if !status.success() {
+      return Err(Error::EditorFailed {
+        command: shell_words::join(&args),
+        status,
+      });
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn locations() -> Vec<Location> {
+    vec![
+      Location::new(Path::new("/zk/a.md")),
+      Location {
+        path: "/zk/b c.md".into(),
+        line: 3,
+        col: 7,
+      },
+    ]
+  }
+
+  #[test]
+  fn args() {
+    let cases = vec![
+      ("vim", vec!["vim", "/zk/a.md", "/zk/b c.md"]),
+      (
+        "code --wait",
+        vec!["code", "--wait", "/zk/a.md", "/zk/b c.md"],
+      ),
+      (
+        "nvim +{line} {path}",
+        vec!["nvim", "+1", "/zk/a.md", "+3", "/zk/b c.md"],
+      ),
+      (
+        "code --wait --goto '{path}:{line}:{col}' --new-window",
+        vec![
+          "code",
+          "--wait",
+          "--goto",
+          "/zk/a.md:1:1",
+          "/zk/b c.md:3:7",
+          "--new-window",
+        ],
+      ),
+    ];
+
+    for (command, want) in cases {
+      assert_eq!(
+        Editor::parse(command).unwrap().args(&locations()),
+        want,
+        "{}",
+        command
+      );
+    }
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert!(Editor::parse("").is_err());
+    assert!(Editor::parse("vim 'unterminated").is_err());
+  }
+}
@@ -3,6 +3,13 @@ use crate::common::*;
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)))]
 pub enum Error {
+  #[snafu(display(
+    "Invalid age `{}`, expected e.g. `30d`, `2w` or `12h`",
+    text
+  ))]
+  AgeInvalid { text: String },
+  #[snafu(display("No note links to `{}`", name))]
+  BacklinksNotFound { name: String },
   #[snafu(
     context(false),
     display("Unable to fetch base directory: {}", source)
@@ -10,31 +17,115 @@ pub enum Error {
   BaseDirectories { source: xdg::BaseDirectoriesError },
   #[snafu(display("Failed to send `SkimItem` over crossbeam_channel"))]
   ChannelSend,
-  #[snafu(
-    context(false),
-    display("Failed to Deserialize TOML configuration file: {}", source)
-  )]
+  #[snafu(display(
+    "Failed to parse configuration file `{}`: {}",
+    path.display(),
+    source
+  ))]
+  ConfigInvalid {
+    path: PathBuf,
+    source: toml::de::Error,
+  },
+  #[snafu(display(
+    "Invalid date `{}`: expected `YYYY-MM-DD`, `today` or `yesterday`",
+    text
+  ))]
+  DateInvalid { text: String },
+  #[snafu(context(false), display("Invalid configuration: {}", source))]
   DeserializeConfig { source: toml::de::Error },
+  #[snafu(display("Found {} problems in the vault", count))]
+  Diagnosis { count: usize },
+  #[snafu(display("Editor `{}` exited with {}", command, status))]
+  EditorFailed {
+    command: String,
+    status: process::ExitStatus,
+  },
+  #[snafu(display("Invalid editor command `{}`", command))]
+  EditorInvalid { command: String },
+  #[snafu(display(
+    "`{}` was changed since, undoing or redoing would overwrite it",
+    path.display()
+  ))]
+  HistoryConflict { path: PathBuf },
+  #[snafu(display(
+    "Failed to parse the undo history `{}`, move it aside to start a new \
+     one: {}",
+    path.display(),
+    source
+  ))]
+  HistoryInvalid {
+    path: PathBuf,
+    source: serde_json::Error,
+  },
+  #[snafu(display(
+    "Note id prefix `{}` is ambiguous, it matches {} notes",
+    prefix,
+    count
+  ))]
+  IdAmbiguous { prefix: String, count: usize },
+  #[snafu(display("No note id starts with `{}`", prefix))]
+  IdNotFound { prefix: String },
   #[snafu(display("Invalid note id: {}", id))]
   InvalidNoteId { id: String },
   #[snafu(context(false), display("IO Error: {}", source))]
   Io { source: io::Error },
+  #[snafu(context(false), display("JSON se/de error: {}", source))]
+  Json { source: serde_json::Error },
   #[snafu(display("Note already contains a link to `{}`", link))]
   LinkExists { link: String },
   #[snafu(display("Link `{}` does not exist on note `{}`", link, name))]
   LinkMissing { link: String, name: String },
+  #[snafu(display("A note already exists at `{}`", path.display()))]
+  NoteExists { path: PathBuf },
   #[snafu(display("Note with name `{}` does not exist", name))]
   NoteNotFound { name: String },
   #[snafu(display("No note was selected"))]
   NoteNotSelected,
+  #[snafu(display("No note matches `{}`", query))]
+  QueryNotFound { query: String },
+  #[snafu(display("Nothing to redo"))]
+  RedoEmpty,
+  #[snafu(display(
+    "Invalid sequence `{}`: expected alternating numbers and letters like `1a2`",
+    text
+  ))]
+  SequenceInvalid { text: String },
+  #[snafu(
+    context(false),
+    display("Failed to serialize configuration: {}", source)
+  )]
+  SerializeConfig { source: toml::ser::Error },
   #[snafu(display("Error building `skim` options"))]
   SkimOptions,
   #[snafu(display("Note already contains the tag `{}`", tag))]
   TagExists { tag: String },
   #[snafu(display("Tag `{}` does not exist on note `{}`", tag, name))]
   TagMissing { tag: String, name: String },
-  #[snafu(display("No note with tag `{}` exists", tag))]
+  #[snafu(display("No note matches the tag query `{}`", tag))]
   TagNotFound { tag: String },
+  #[snafu(display("Invalid tag query: {}", message))]
+  TagQuery { message: String },
+  #[snafu(display(
+    "Template `{}` not found in {}",
+    name,
+    dirs
+      .iter()
+      .map(|dir| format!("`{}`", dir.display()))
+      .collect::<Vec<_>>()
+      .join(" or ")
+  ))]
+  TemplateNotFound { name: String, dirs: Vec<PathBuf> },
+  #[snafu(display(
+    "Selecting between notes requires a terminal, use one of `--first`, \
+     `--all`, `--latest` or `--id` instead"
+  ))]
+  TerminalRequired,
+  #[snafu(display("The trash is empty"))]
+  TrashEmpty,
+  #[snafu(display("Nothing to undo"))]
+  UndoEmpty,
+  #[snafu(display("No vault named `{}` is configured", name))]
+  VaultNotFound { name: String },
   #[snafu(context(false), display("Walkdir Error: {}", source))]
   Walkdir { source: walkdir::Error },
   #[snafu(context(false), display("YAML se/de error: {}", source))]
@@ -0,0 +1,159 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+  /// A JSON array
+  Json,
+  /// One JSON object per line
+  Ndjson,
+  /// One note path per line
+  #[default]
+  Paths,
+  /// An aligned, human readable table
+  Table,
+}
+
+impl Format {
+  pub(crate) fn write(
+    self,
+    listings: &[Listing],
+    out: &mut impl Write,
+  ) -> Result<()> {
+    match self {
+      Format::Json => {
+        serde_json::to_writer_pretty(&mut *out, listings)?;
+        writeln!(out)?;
+      }
+      Format::Ndjson => {
+        for listing in listings {
+          serde_json::to_writer(&mut *out, listing)?;
+          writeln!(out)?;
+        }
+      }
+      Format::Paths => {
+        for listing in listings {
+          writeln!(out, "{}", listing.path.display())?;
+        }
+      }
+      Format::Table => {
+        let header = ["PREFIX", "NAME", "TITLE", "TAGS", "LINKS", "BACKLINKS"];
+
+        let rows = listings
+          .iter()
+          .map(|listing| {
+            [
+              listing.prefix.clone(),
+              listing.name.clone(),
+              listing.title.clone().unwrap_or_default(),
+              listing.tags.join(","),
+              listing.links.len().to_string(),
+              listing.backlinks.len().to_string(),
+            ]
+          })
+          .collect::<Vec<_>>();
+
+        let mut widths = header.map(str::len);
+
+        for row in &rows {
+          for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+          }
+        }
+
+        for row in iter::once(header.map(String::from)).chain(rows) {
+          let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+          writeln!(out, "{}", line.trim_end())?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn listings() -> Vec<Listing> {
+    vec![
+      Listing {
+        prefix: "1625104269".into(),
+        name: "a".into(),
+        title: Some("The a note".into()),
+        path: "/zk/1625104269-a.md".into(),
+        tags: vec!["code".into(), "software".into()],
+        links: vec!["1625104326-b.md".into()],
+        backlinks: vec![],
+        created: timestamp::parse("2021-07-01T01:51:09Z"),
+        updated: None,
+      },
+      Listing {
+        prefix: "1625104326".into(),
+        name: "b".into(),
+        title: None,
+        path: "/zk/1625104326-b.md".into(),
+        tags: vec![],
+        links: vec![],
+        backlinks: vec!["1625104269-a.md".into()],
+        created: None,
+        updated: None,
+      },
+    ]
+  }
+
+  fn render(format: Format) -> String {
+    let mut out = Vec::new();
+    format.write(&listings(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn paths() {
+    assert_eq!(
+      render(Format::Paths),
+      "/zk/1625104269-a.md\n/zk/1625104326-b.md\n"
+    );
+  }
+
+  #[test]
+  fn table() {
+    assert_eq!(
+      render(Format::Table),
+      indoc! {"
+        PREFIX      NAME  TITLE       TAGS           LINKS  BACKLINKS
+        1625104269  a     The a note  code,software  1      0
+        1625104326  b                                0      1
+      "}
+    );
+  }
+
+  #[test]
+  fn ndjson() {
+    let output = render(Format::Ndjson);
+
+    let lines = output.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 2);
+
+    assert_eq!(
+      lines[0],
+      r#"{"prefix":"1625104269","name":"a","title":"The a note","path":"/zk/1625104269-a.md","tags":["code","software"],"links":["1625104326-b.md"],"backlinks":[],"created":"2021-07-01T01:51:09Z","updated":null}"#
+    );
+  }
+
+  #[test]
+  fn json() {
+    let value =
+      serde_json::from_str::<serde_json::Value>(&render(Format::Json)).unwrap();
+
+    assert_eq!(value[1]["name"], "b");
+    assert_eq!(value[1]["backlinks"][0], "1625104269-a.md");
+  }
+}
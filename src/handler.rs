@@ -1,160 +1,852 @@
-use crate::common::*;
+use {crate::common::*, chrono::TimeDelta};
 
 #[derive(Debug)]
 pub(crate) struct Handler {
   pub(crate) config: Config,
   pub(crate) directory: Directory,
+  pub(crate) selection: Selection,
 }
 
 impl Handler {
-  pub(crate) fn new(config: Config, directory: Directory) -> Self {
-    Self { config, directory }
+  pub(crate) fn new(
+    config: Config,
+    directory: Directory,
+    selection: Selection,
+  ) -> Self {
+    Self {
+      config,
+      directory,
+      selection,
+    }
   }
 
-  pub fn create(&self, name: &str) -> Result<()> {
-    self.open(
-      &Note::create(self.directory.path.join(NoteId::new(name).to_string()))?
-        .id
-        .name,
-    )
+  fn select(&self, notes: Vec<Note>) -> Result<Vec<Note>> {
+    self.selection.select(notes)
   }
 
-  pub(crate) fn open(&self, name: &str) -> Result<()> {
-    Search::new(self.directory.find(name)?)
-      .run()?
+  fn select_in_order(&self, notes: Vec<Note>) -> Result<Vec<Note>> {
+    self.selection.select_in_order(notes)
+  }
+
+  /// Create a note as `zk new` does, reading its body from `stdin` when
+  /// asked to.
+  pub fn create(&self, new: NewNote, stdin: impl Read) -> Result<()> {
+    // Resolve every note the new one refers to before writing anything, so a
+    // typo doesn't leave a half-populated note behind.
+    let links = new
+      .links
       .iter()
-      .try_for_each(|note| {
-        Command::new(&self.config.editor).arg(&note.path).status()?;
-        Ok(())
+      .map(|name| {
+        Ok(
+          self
+            .select(self.directory.find(name)?)?
+            .first()
+            .unwrap()
+            .to_owned(),
+        )
       })
+      .collect::<Result<Vec<_>>>()?;
+
+    let body = match new.stdin {
+      Some(_) => Some(io::read_to_string(stdin)?),
+      None => None,
+    };
+
+    let placement = match (&new.after, &new.branch) {
+      (Some(target), _) => Some(self.allocate(target, Sequence::after)?),
+      (None, Some(target)) => Some(self.allocate(target, Sequence::below)?),
+      (None, None) => None,
+    };
+
+    let note = store::transaction(|| {
+      let mut note = self.new_note(
+        &new.name,
+        new.title.as_deref(),
+        new.template.as_deref(),
+        body.as_deref(),
+        Local::now().date_naive(),
+      )?;
+
+      if let Some((sequence, target)) = placement {
+        note.set_sequence(&sequence)?;
+
+        if let Some((mut target, root)) = target {
+          target.set_sequence(&root)?;
+        }
+      }
+
+      for tag in &new.tags {
+        if !note.has_exact_tag(tag) {
+          note.add_tag(tag)?;
+        }
+      }
+
+      let names = self.directory.names()?;
+
+      for mut link in links {
+        if !note.has_link(&link.id.to_string(), &names) {
+          note.add_link(&link.id.to_string(), &names)?;
+        }
+
+        if !link.has_link(&note.id.to_string(), &names) {
+          link.add_link(&note.id.to_string(), &names)?;
+        }
+      }
+
+      Ok(note)
+    })?;
+
+    if new.edit() {
+      self.edit(&[note])
+    } else {
+      println!("{}", note.path.display());
+      Ok(())
+    }
+  }
+
+  /// Write a new note called `name`, optionally from a template whose
+  /// `{{date}}` placeholder is filled in with `date`.
+  ///
+  /// A `body`, read from the reader given to `create`, fills the template's
+  /// `{{stdin}}` placeholder, or is appended to the note if there is none.
+  /// Without one, `{{stdin}}` is left empty.
+  fn new_note(
+    &self,
+    name: &str,
+    title: Option<&str>,
+    template: Option<&str>,
+    mut body: Option<&str>,
+    date: NaiveDate,
+  ) -> Result<Note> {
+    let id = self.directory.new_id(name, self.config.id_scheme)?;
+
+    let mut text = match template {
+      Some(template) => {
+        let template = Template::load(
+          template,
+          &[
+            self.directory.data_dir().join("templates"),
+            self.config.templates.expand(),
+          ],
+        )?;
+
+        let stdin = if template.uses("stdin") {
+          body.take().unwrap_or_default().to_owned()
+        } else {
+          String::new()
+        };
+
+        template.render(|key| match key {
+          "date" => Some(date.format("%Y-%m-%d").to_string()),
+          "id" => Some(id.stem()),
+          "name" => Some(name.to_owned()),
+          "stdin" => Some(stdin.clone()),
+          "title" => Some(title.unwrap_or(name).to_owned()),
+          _ => None,
+        })
+      }
+      None => String::new(),
+    };
+
+    if let Some(body) = body {
+      text.push_str(body);
+    }
+
+    let mut note =
+      Note::create(self.directory.path.join(id.to_string()), &text)?;
+
+    if let Some(title) = title {
+      note.set_title(title)?;
+    }
+
+    Ok(note)
+  }
+
+  /// Open the journal note for `period`, creating it if needed.
+  pub(crate) fn journal(&self, period: Period) -> Result<()> {
+    self.edit(&[self.journal_note(period)?])
+  }
+
+  /// The journal note for `period`, created and linked with the most recent
+  /// earlier one if it doesn't exist yet.
+  fn journal_note(&self, period: Period) -> Result<Note> {
+    let journal = &self.config.journal;
+
+    let notes = self.directory.notes()?;
+
+    let find = |period: Period| {
+      let name = period.name(journal);
+      notes.iter().find(|note| note.id.name == name).cloned()
+    };
+
+    if let Some(note) = find(period) {
+      return Ok(note);
+    }
+
+    let previous = period.before().take(Period::LOOKBACK).find_map(find);
+
+    store::transaction(|| {
+      let mut note = self.new_note(
+        &period.name(journal),
+        None,
+        period.template(journal),
+        None,
+        period.date(),
+      )?;
+
+      if let Some(mut previous) = previous {
+        let names = self.directory.names()?;
+        note.add_link(&previous.id.to_string(), &names)?;
+        previous.add_link(&note.id.to_string(), &names)?;
+      }
+
+      Ok(note)
+    })
+  }
+
+  /// Open `notes` in a single editor invocation.
+  fn edit(&self, notes: &[Note]) -> Result<()> {
+    self.config.editor()?.open(
+      &notes
+        .iter()
+        .map(|note| Location::new(&note.path))
+        .collect::<Vec<_>>(),
+    )
+  }
+
+  /// The sequence for a note placed relative to `target` by `place`, which
+  /// is given the sequences already taken. A target without a sequence is
+  /// first given the next free root, which is returned along with it for the
+  /// caller to write once the new note exists.
+  fn allocate(
+    &self,
+    target: &str,
+    place: fn(&Sequence, &BTreeSet<Sequence>) -> Sequence,
+  ) -> Result<(Sequence, Option<(Note, Sequence)>)> {
+    let mut taken = self
+      .directory
+      .notes()?
+      .iter()
+      .filter_map(|note| note.matter.sequence.clone())
+      .collect::<BTreeSet<_>>();
+
+    let target = self
+      .select(self.directory.find(target)?)?
+      .first()
+      .unwrap()
+      .to_owned();
+
+    let (base, unnumbered) = match target.matter.sequence.clone() {
+      Some(sequence) => (sequence, None),
+      None => {
+        let root = taken
+          .iter()
+          .rev()
+          .find(|sequence| sequence.parent().is_none())
+          .map_or(Sequence::root(1), Sequence::next_sibling);
+
+        taken.insert(root.clone());
+
+        (root.clone(), Some((target, root)))
+      }
+    };
+
+    Ok((place(&base, &taken), unnumbered))
+  }
+
+  pub(crate) fn sequence(&self) -> Result<()> {
+    Ok(
+      SequenceTree::new(&self.directory.notes()?)
+        .write(&mut io::stdout().lock())?,
+    )
+  }
+
+  pub(crate) fn open(&self, name: &str) -> Result<()> {
+    self.edit(&self.select(self.directory.find(name)?)?)
   }
 
   pub(crate) fn link(&self, left: &str, right: &str) -> Result<()> {
-    let mut left = Search::new(self.directory.find(left)?)
-      .run()?
+    let mut left = self
+      .select(self.directory.find(left)?)?
       .first()
       .unwrap()
       .to_owned();
 
-    let mut right = Search::new(self.directory.find(right)?)
-      .run()?
+    let mut right = self
+      .select(self.directory.find(right)?)?
       .first()
       .unwrap()
       .to_owned();
 
-    left.add_link(&right.id.to_string())?;
-    right.add_link(&left.id.to_string())?;
+    let names = self.directory.names()?;
+
+    store::transaction(|| {
+      left.add_link(&right.id.to_string(), &names)?;
+      right.add_link(&left.id.to_string(), &names)
+    })?;
 
     Ok(())
   }
 
-  pub(crate) fn find(&self, tag: &str) -> Result<()> {
-    Search::new(self.directory.find_by_tag(tag)?)
-      .run()?
-      .iter()
-      .try_for_each(|note| {
-        Command::new(&self.config.editor).arg(&note.path).status()?;
-        Ok(())
-      })
+  pub(crate) fn find(&self, query: &Query) -> Result<()> {
+    self.edit(&self.select(self.directory.find_by_query(query)?)?)
   }
 
-  pub(crate) fn search(&self) -> Result<()> {
-    Search::new(self.directory.notes()?)
-      .run()?
+  pub(crate) fn list(
+    &self,
+    tag: Option<&Query>,
+    name: Option<&str>,
+    link: Option<&str>,
+    sort: ListSort,
+    format: Format,
+  ) -> Result<()> {
+    let notes = self.directory.notes()?;
+
+    let mut links = vec![Vec::new(); notes.len()];
+    let mut backlinks = vec![Vec::new(); notes.len()];
+
+    for (from, to) in Directory::edges(&notes) {
+      links[from].push(to);
+      backlinks[to].push(from);
+    }
+
+    let ids = |indices: &[usize]| {
+      indices
+        .iter()
+        .map(|&index| notes[index].id.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    let listings = notes
       .iter()
-      .try_for_each(|note| {
-        Command::new(&self.config.editor).arg(&note.path).status()?;
-        Ok(())
+      .enumerate()
+      .filter(|(_, note)| tag.is_none_or(|query| query.matches(note)))
+      .filter(|(_, note)| name.is_none_or(|name| note.id.name == name))
+      .filter(|(index, _)| {
+        link.is_none_or(|link| {
+          links[*index].iter().any(|&to| {
+            let id = &notes[to].id;
+            id.name == link || id.is_stem(link) || id.to_string() == link
+          })
+        })
       })
+      .map(|(index, note)| {
+        Listing::new(note, ids(&links[index]), ids(&backlinks[index]))
+      })
+      .collect::<Vec<_>>();
+
+    format.write(&sort.sort(listings), &mut io::stdout().lock())
+  }
+
+  pub(crate) fn grep(&self, query: &str, limit: Option<usize>) -> Result<()> {
+    let notes = self.directory.notes()?;
+
+    let mut out = io::stdout().lock();
+
+    for (index, score) in TextIndex::new(&notes)
+      .search(query)
+      .into_iter()
+      .take(limit.unwrap_or(usize::MAX))
+    {
+      writeln!(out, "{:>8.3}  {}", score, notes[index].path.display())?;
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn search(&self, query: Option<&str>) -> Result<()> {
+    match query {
+      Some(query) => {
+        let notes = self.select_in_order(self.rank(query)?)?;
+
+        let mut locations = Vec::new();
+
+        for note in &notes {
+          let text = fs::read_to_string(&note.path)?;
+
+          locations.push(match TextIndex::locate(&text, query) {
+            Some((line, col)) => Location::at(&note.path, line, col),
+            None => Location::new(&note.path),
+          });
+        }
+
+        self.config.editor()?.open(&locations)
+      }
+      None => self.edit(&self.select(self.directory.notes()?)?),
+    }
+  }
+
+  pub(crate) fn doctor(&self, fix: bool) -> Result<()> {
+    let (problems, names) = Problem::diagnose(&self.directory)?;
+
+    let fixed = |problem: &Problem| fix && problem.is_fixable();
+
+    store::transaction(|| {
+      problems
+        .iter()
+        .filter(|problem| fixed(problem))
+        .try_for_each(|problem| problem.fix(&names))
+    })?;
+
+    let mut remaining = 0;
+
+    for problem in &problems {
+      if fixed(problem) {
+        println!("{} {}", "fixed".green(), problem);
+      } else {
+        println!("{}", problem);
+        remaining += 1;
+      }
+    }
+
+    if remaining > 0 {
+      return Err(Error::Diagnosis { count: remaining });
+    }
+
+    Ok(())
   }
 
   pub fn dir(&self) {
     println!("{}", self.directory.path.expand().display());
   }
 
+  /// List the configured vaults, marking the one in use with `*`.
+  pub(crate) fn vaults(&self) -> Result<()> {
+    let width = self
+      .directory
+      .vaults
+      .keys()
+      .map(String::len)
+      .max()
+      .unwrap_or_default();
+
+    let mut out = io::stdout().lock();
+
+    for (name, path) in &self.directory.vaults {
+      writeln!(
+        out,
+        "{} {:width$}  {}",
+        if *path == self.directory.path {
+          '*'
+        } else {
+          ' '
+        },
+        name,
+        path.display(),
+        width = width
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Rename a note, keeping its id prefix, and rewrite every link to it.
+  ///
+  /// The renamed copy is written before any link is rewritten and the
+  /// original is only removed afterwards, so an interrupted rename leaves
+  /// every link pointing at a note that still exists. A rename that fails is
+  /// rolled back.
+  pub(crate) fn rename(&self, name: &str, new_name: &str) -> Result<()> {
+    let note = self
+      .select(self.directory.find(name)?)?
+      .first()
+      .ok_or(Error::NoteNotSelected)?
+      .to_owned();
+
+    let notes = self.directory.notes()?;
+    let names = Names::new(notes.iter().map(|note| &note.id));
+
+    let (renamed, relinked) = store::transaction(|| {
+      let mut renamed = note.renamed(new_name)?;
+
+      if renamed.has_link(&note.id.to_string(), &names) {
+        let id = renamed.id.clone();
+        renamed.relink(&note.id, &id, &names)?;
+      }
+
+      let relinked = notes
+        .into_iter()
+        .filter(|other| {
+          other.path != note.path
+            && other.path != renamed.path
+            && other.has_link(&note.id.to_string(), &names)
+        })
+        .map(|mut other| other.relink(&note.id, &renamed.id, &names))
+        .collect::<Result<Vec<_>>>()?;
+
+      note.remove()?;
+
+      Ok((renamed, relinked))
+    })?;
+
+    for other in relinked {
+      println!("{}", other.path.display());
+    }
+
+    println!("{}", renamed.path.display());
+
+    Ok(())
+  }
+
+  /// Move notes into the trash, removing the links other notes have to them.
   pub(crate) fn remove(&self, name: &str) -> Result<()> {
-    Search::new(self.directory.find(name)?)
-      .run()?
-      .iter()
-      .try_for_each(|item| {
-        self
-          .directory
-          .notes()?
+    let trash = Trash::new(&self.directory);
+
+    let selected = self.select(self.directory.find(name)?)?;
+
+    store::transaction(|| {
+      let mut notes = self.directory.notes()?;
+
+      selected.iter().try_for_each(|item| {
+        let names = Names::new(notes.iter().map(|note| &note.id));
+
+        notes.retain(|note| note.path != item.path);
+
+        let links = notes
           .iter_mut()
-          .filter(|note| note.has_link(&item.id.to_string()))
-          .try_for_each(|note| -> Result<()> {
-            note.remove_link(&item.id.to_string())?;
-            Ok(())
-          })?;
-        item.remove()
+          .filter(|note| note.has_link(&item.id.to_string(), &names))
+          .map(|note| RemovedLink::remove(note, &item.id, &names))
+          .collect::<Result<Vec<_>>>()?;
+
+        trash.put(item, links)
       })
+    })
+  }
+
+  /// Bring notes back from the trash along with the links to them, printing
+  /// where each one is restored to.
+  pub(crate) fn restore(&self, name: Option<&str>) -> Result<()> {
+    let trash = Trash::new(&self.directory);
+
+    let entries = trash
+      .entries()?
+      .into_iter()
+      .filter(|entry| {
+        name.is_none_or(|name| {
+          NoteId::parse(entry.path.unwrapped_filename())
+            .is_some_and(|id| id.name == name)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+      return Err(match name {
+        Some(name) => Error::NoteNotFound {
+          name: name.to_owned(),
+        },
+        None => Error::TrashEmpty,
+      });
+    }
+
+    let notes = entries
+      .iter()
+      .map(|entry| Note::from(entry.note()))
+      .collect::<Result<Vec<_>>>()?;
+
+    let selected = self.select_in_order(notes)?;
+
+    let paths = store::transaction(|| {
+      let mut notes = self.directory.notes()?;
+
+      selected
+        .iter()
+        .filter_map(|note| {
+          entries.iter().find(|entry| entry.note() == note.path)
+        })
+        .map(|entry| {
+          let path = trash.restore(entry, &mut notes)?;
+          notes.push(Note::from(path.clone())?);
+          Ok(path)
+        })
+        .collect::<Result<Vec<_>>>()
+    })?;
+
+    for path in paths {
+      println!("{}", path.display());
+    }
+
+    Ok(())
+  }
+
+  /// Revert the most recent command that changed the vault.
+  pub(crate) fn undo(&self) -> Result<()> {
+    let mut history = History::load(&self.directory.data_dir())?;
+    let operation = history.undo(&self.directory.path)?;
+    history.save(&self.directory.data_dir())?;
+
+    println!("Undid {}", operation);
+
+    Ok(())
+  }
+
+  /// Replay the most recently undone command.
+  pub(crate) fn redo(&self) -> Result<()> {
+    let mut history = History::load(&self.directory.data_dir())?;
+    let operation = history.redo(&self.directory.path)?;
+    history.save(&self.directory.data_dir())?;
+
+    println!("Redid {}", operation);
+
+    Ok(())
+  }
+
+  /// List the notes in the trash, oldest first.
+  pub(crate) fn trash(&self) -> Result<()> {
+    let mut out = io::stdout().lock();
+
+    for entry in Trash::new(&self.directory).entries()? {
+      writeln!(
+        out,
+        "{}  {}",
+        entry.deleted.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+        entry.path.display()
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Permanently delete the notes trashed more than `age` ago, or every one
+  /// without an age, printing each deleted note.
+  pub(crate) fn empty_trash(&self, age: Option<TimeDelta>) -> Result<()> {
+    let cutoff = match age {
+      Some(age) => Utc::now() - age,
+      None => DateTime::<Utc>::MAX_UTC,
+    };
+
+    let trash = Trash::new(&self.directory);
+
+    let data_dir = self.directory.data_dir();
+    let mut history = History::load(&data_dir)?;
+
+    let entries = store::transaction(|| trash.empty(cutoff))?;
+
+    for entry in &entries {
+      history.forget(
+        entry
+          .dir()
+          .strip_prefix(&self.directory.path)
+          .unwrap_or(entry.dir()),
+      );
+      println!("{}", entry.path.display());
+    }
+
+    history.save(&data_dir)?;
+
+    Ok(())
   }
 
   pub(crate) fn remove_link(&self, left: &str, right: &str) -> Result<()> {
-    let mut left = Search::new(self.directory.find(left)?)
-      .run()?
+    let mut left = self
+      .select(self.directory.find(left)?)?
       .first()
       .unwrap()
       .to_owned();
 
-    let mut right = Search::new(self.directory.find(right)?)
-      .run()?
+    let mut right = self
+      .select(self.directory.find(right)?)?
       .first()
       .unwrap()
       .to_owned();
 
-    left.remove_link(&right.id.to_string())?;
-    right.remove_link(&left.id.to_string())?;
+    let names = self.directory.names()?;
+
+    store::transaction(|| {
+      left.remove_link(&right.id.to_string(), &names)?;
+      right.remove_link(&left.id.to_string(), &names)
+    })?;
 
     Ok(())
   }
 
+  /// Replace every tag within one of `sources` with the matching tag under
+  /// `target` across the whole vault, printing each note that changes.
+  pub(crate) fn retag(
+    &self,
+    sources: &[&str],
+    target: &str,
+    dry_run: bool,
+  ) -> Result<()> {
+    let notes = self.directory.notes()?;
+
+    let changed = store::transaction(|| {
+      let mut changed = Vec::new();
+
+      for mut note in notes {
+        if let Some(tags) = note.retagged(sources, target) {
+          if !dry_run {
+            note.set_tags(tags)?;
+          }
+
+          changed.push(note.path);
+        }
+      }
+
+      Ok(changed)
+    })?;
+
+    for path in &changed {
+      println!("{}", path.display());
+    }
+
+    let changed = changed.len();
+
+    if changed == 0 {
+      return Err(Error::TagNotFound {
+        tag: sources.join(" or "),
+      });
+    }
+
+    if dry_run {
+      eprintln!("{} notes would be updated", changed);
+    } else {
+      eprintln!("Updated {} notes", changed);
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn tags(
+    &self,
+    tree: bool,
+    sort: TagSort,
+    json: bool,
+  ) -> Result<()> {
+    let notes = self.directory.notes()?;
+
+    let mut out = io::stdout().lock();
+
+    if tree {
+      return Ok(TagTree::new(&notes).write(&mut out)?);
+    }
+
+    let summaries = TagSummary::collect(&notes, sort);
+
+    if json {
+      serde_json::to_writer_pretty(&mut out, &summaries)?;
+      writeln!(out)?;
+    } else {
+      TagSummary::write(&summaries, &mut out)?;
+    }
+
+    Ok(())
+  }
+
+  /// Notes matching a full-text `query`, best match first.
+  fn rank(&self, query: &str) -> Result<Vec<Note>> {
+    let notes = self.directory.notes()?;
+
+    let ranked = TextIndex::new(&notes)
+      .search(query)
+      .into_iter()
+      .map(|(index, _)| notes[index].clone())
+      .collect::<Vec<_>>();
+
+    if ranked.is_empty() {
+      return Err(Error::QueryNotFound {
+        query: query.to_owned(),
+      });
+    }
+
+    Ok(ranked)
+  }
+
   pub(crate) fn tag(&self, name: &str, tag: &str) -> Result<()> {
-    Search::new(self.directory.find(name)?)
-      .run()?
-      .iter_mut()
-      .filter(|note| !note.has_tag(tag))
-      .try_for_each(|note| {
-        note.add_tag(tag)?;
-        Ok(())
-      })
+    let mut notes = self.select(self.directory.find(name)?)?;
+
+    store::transaction(|| {
+      notes
+        .iter_mut()
+        .filter(|note| !note.has_exact_tag(tag))
+        .try_for_each(|note| {
+          note.add_tag(tag)?;
+          Ok(())
+        })
+    })
   }
 
-  pub(crate) fn remove_tag(&self, name: &str, tag: &str) -> Result<()> {
-    Search::new(self.directory.find(name)?)
-      .run()?
-      .iter_mut()
-      .filter(|note| note.has_tag(tag))
-      .try_for_each(|note| {
-        note.remove_tag(tag)?;
-        Ok(())
-      })
+  pub(crate) fn remove_tag(
+    &self,
+    name: &str,
+    tag: &str,
+    descendants: bool,
+  ) -> Result<()> {
+    let mut notes = self.select(self.directory.find(name)?)?;
+
+    store::transaction(|| {
+      notes
+        .iter_mut()
+        .filter(|note| {
+          if descendants {
+            note.has_tag(tag)
+          } else {
+            note.has_exact_tag(tag)
+          }
+        })
+        .try_for_each(|note| {
+          note.remove_tag(tag, descendants)?;
+          Ok(())
+        })
+    })
+  }
+
+  pub(crate) fn backlinks(&self, name: &str) -> Result<()> {
+    let note = self
+      .select(self.directory.find(name)?)?
+      .first()
+      .ok_or(Error::NoteNotSelected)?
+      .to_owned();
+
+    let backlinks = self.directory.backlinks_of(&note)?;
+
+    if backlinks.is_empty() {
+      return Err(Error::BacklinksNotFound {
+        name: note.id.to_string(),
+      });
+    }
+
+    self.edit(&self.select(backlinks)?)
   }
 
   pub(crate) fn explore(&self, name: &str) -> Result<()> {
-    let note = Search::new(self.directory.find(name)?)
-      .run()?
+    let note = self
+      .select(self.directory.find(name)?)?
       .first()
       .ok_or(Error::NoteNotSelected)?
       .to_owned();
 
+    self.explore_note(&note, false)
+  }
+
+  fn explore_note(&self, note: &Note, backlinks: bool) -> Result<()> {
+    if !Selection::is_terminal() {
+      return Err(Error::TerminalRequired);
+    }
+
+    let header = if backlinks {
+      format!("Backlinks of {}", note.id)
+    } else {
+      format!("Links of {}", note.id)
+    };
+
     let options = SkimOptionsBuilder::default()
       .height(Some("100%"))
       .preview(Some(""))
       .multi(true)
-      .bind(vec!["ctrl-e:abort", "Enter:accept"])
+      .header(Some(&header))
+      .bind(vec!["ctrl-e:abort", "ctrl-b:abort", "Enter:accept"])
       .build()
       .unwrap();
 
     let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
 
-    note
-      .matter
-      .links
-      .unwrap_or_default()
-      .iter()
-      .map(|link| Note::from(self.directory.path.join(link)))
-      .collect::<Result<Vec<_>, _>>()?
+    let items = if backlinks {
+      self.directory.backlinks_of(note)?
+    } else {
+      self.directory.links_of(note)?
+    };
+
+    items
       .iter()
       .cloned()
       .try_for_each(|note| tx.send(Arc::new(note)))
@@ -178,14 +870,11 @@ impl Handler {
       match out.final_key {
         Key::Enter => {
           if let Some(item) = selected_items.first() {
-            self.explore(&item.id.name)?
-          }
-        }
-        Key::Ctrl('e') => {
-          if let Some(item) = selected_items.first() {
-            self.open(&item.id.name)?
+            self.explore_note(item, backlinks)?
           }
         }
+        Key::Ctrl('b') => self.explore_note(note, !backlinks)?,
+        Key::Ctrl('e') => self.edit(&selected_items)?,
         _ => {}
       };
     }
@@ -193,3 +882,219 @@ impl Handler {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn create_in_sequence() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+      let f = create_note("f").unwrap();
+
+      let new = |args: &[&str]| {
+        handler().create(
+          NewNote::try_parse_from(
+            ["new"].iter().chain(args).chain(&["--no-edit"]),
+          )
+          .unwrap(),
+          io::empty(),
+        )
+      };
+
+      new(&["b", "--branch", "a"]).unwrap();
+      new(&["c", "--after", "a"]).unwrap();
+      new(&["d", "--after", "b"]).unwrap();
+      new(&["e", "--after", "b"]).unwrap();
+
+      let sequence = |name: &str| {
+        handler().directory.find(name).unwrap()[0]
+          .matter
+          .sequence
+          .as_ref()
+          .map(Sequence::to_string)
+      };
+
+      for (name, want) in [
+        ("a", "1"),
+        ("b", "1a"),
+        ("c", "2"),
+        ("d", "1b"),
+        ("e", "1a1"),
+      ] {
+        assert_eq!(sequence(name).as_deref(), Some(want), "{}", name);
+      }
+
+      assert!(matches!(
+        new(&["g", "--branch", "f", "--template", "missing"]),
+        Err(Error::TemplateNotFound { .. })
+      ));
+
+      assert_eq!(Note::from(f.path).unwrap().matter.sequence, None);
+    });
+  }
+
+  #[test]
+  fn create_headless() {
+    in_temp_dir!({
+      let a = create_note("a").unwrap();
+      let b = create_note("b").unwrap();
+
+      handler()
+        .create(
+          NewNote::try_parse_from([
+            "new", "c", "-", "--tag", "rust", "--tag", "lang/go", "--link",
+            "a", "--link", "b",
+          ])
+          .unwrap(),
+          "Captured from a script.\n".as_bytes(),
+        )
+        .unwrap();
+
+      let c = handler().directory.find("c").unwrap().remove(0);
+
+      assert_eq!(c.content, "Captured from a script.\n");
+      assert_eq!(c.matter.tags, Some(vec!["rust".into(), "lang/go".into()]));
+      assert!(c.has_link(&a.id.to_string(), &Names::default()));
+      assert!(c.has_link(&b.id.to_string(), &Names::default()));
+
+      for note in [a, b] {
+        assert!(Note::from(note.path)
+          .unwrap()
+          .has_link(&c.id.to_string(), &Names::default()));
+      }
+    });
+  }
+
+  #[test]
+  fn create_from_template_with_stdin() {
+    in_temp_dir!({
+      fs::create_dir_all(".zk/templates").unwrap();
+      fs::write(".zk/templates/capture.md", "# {{name}}\n\n{{stdin}}\n")
+        .unwrap();
+
+      let new = |args: &[&str], stdin: &str| {
+        handler()
+          .create(
+            NewNote::try_parse_from(
+              ["new", "--template", "capture", "--no-edit"]
+                .iter()
+                .chain(args),
+            )
+            .unwrap(),
+            stdin.as_bytes(),
+          )
+          .unwrap();
+      };
+
+      new(&["a", "-"], "Piped in.");
+      new(&["b"], "Not read.");
+
+      let content =
+        |name: &str| handler().directory.find(name).unwrap().remove(0).content;
+
+      assert_eq!(content("a"), "# a\n\nPiped in.\n");
+      assert_eq!(content("b"), "# b\n\n\n");
+    });
+  }
+
+  #[test]
+  fn journal_note() {
+    in_temp_dir!({
+      let day = |text: &str| Period::parse(text).unwrap();
+
+      let first = handler().journal_note(day("2021-07-01")).unwrap();
+      let second = handler().journal_note(day("2021-07-03")).unwrap();
+
+      assert_eq!(second.id.name, "2021-07-03");
+      assert!(second.has_link(&first.id.to_string(), &Names::default()));
+      assert!(Note::from(first.path.clone())
+        .unwrap()
+        .has_link(&second.id.to_string(), &Names::default()));
+
+      assert_eq!(
+        handler().journal_note(day("2021-07-03")).unwrap().path,
+        second.path
+      );
+      assert_eq!(handler().directory.notes().unwrap().len(), 2);
+    });
+  }
+
+  #[test]
+  fn create_headless_missing_link() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+
+      assert!(matches!(
+        handler().create(
+          NewNote::try_parse_from(["new", "c", "-", "--link", "missing"])
+            .unwrap(),
+          "body".as_bytes(),
+        ),
+        Err(Error::NoteNotFound { .. })
+      ));
+
+      assert_eq!(handler().directory.notes().unwrap().len(), 1);
+    });
+  }
+
+  #[test]
+  fn rename_leaves_ambiguous_links() {
+    in_temp_dir!({
+      create_note("a").unwrap();
+      create_note("a").unwrap();
+      let mut c = create_note("c").unwrap();
+
+      c.set_content("See [[a]].\n").unwrap();
+
+      let text = fs::read_to_string(&c.path).unwrap();
+
+      handler().rename("a", "b").unwrap();
+
+      assert_eq!(fs::read_to_string(&c.path).unwrap(), text);
+
+      create_note("a").unwrap();
+      handler().remove("a").unwrap();
+
+      assert_eq!(fs::read_to_string(&c.path).unwrap(), text);
+    });
+  }
+
+  #[test]
+  fn link_rolls_back() {
+    in_temp_dir!({
+      let a = create_note("a").unwrap();
+      let mut b = create_note("b").unwrap();
+
+      b.add_link(&a.id.to_string(), &Names::default()).unwrap();
+
+      let before = fs::read_to_string(&a.path).unwrap();
+
+      assert!(matches!(
+        handler().link("a", "b"),
+        Err(Error::LinkExists { .. })
+      ));
+
+      assert_eq!(fs::read_to_string(&a.path).unwrap(), before);
+    });
+  }
+
+  #[test]
+  fn doctor_with_malformed_note() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.add_link("1625104999-x", &Names::default()).unwrap();
+
+      fs::write("1625104326-b.md", "---\nname: [\n---\n").unwrap();
+
+      assert!(matches!(
+        handler().doctor(true),
+        Err(Error::Diagnosis { count: 1 })
+      ));
+
+      assert!(Note::from(a.path).unwrap().links().is_empty());
+    });
+  }
+}
@@ -0,0 +1,388 @@
+use crate::common::*;
+
+/// A file a command changed, with its contents before and after, `None` when
+/// it didn't exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Change {
+  pub(crate) path: PathBuf,
+  pub(crate) before: Option<String>,
+  pub(crate) after: Option<String>,
+}
+
+/// Everything a single `zk` command changed in the vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Operation {
+  /// The command line, without the program name
+  pub(crate) command: String,
+  pub(crate) time: DateTime<Utc>,
+  /// The changed files, relative to the vault
+  pub(crate) changes: Vec<Change>,
+}
+
+impl Operation {
+  /// Roughly how many bytes the operation takes up in the history file.
+  fn size(&self) -> usize {
+    self.command.len()
+      + self
+        .changes
+        .iter()
+        .map(|change| {
+          change.path.as_os_str().len()
+            + change.before.as_ref().map_or(0, String::len)
+            + change.after.as_ref().map_or(0, String::len)
+        })
+        .sum::<usize>()
+  }
+
+  fn touches(&self, dir: &Path) -> bool {
+    self
+      .changes
+      .iter()
+      .any(|change| change.path.starts_with(dir))
+  }
+}
+
+impl Display for Operation {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "`zk {}`", self.command)
+  }
+}
+
+/// The commands that changed the vault, which `zk undo` reverts and `zk redo`
+/// replays, kept in the data directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct History {
+  done: Vec<Operation>,
+  undone: Vec<Operation>,
+}
+
+impl History {
+  /// How many commands are kept to undo.
+  const LIMIT: usize = 100;
+
+  /// How many bytes of file contents are kept to undo, across all commands.
+  const SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+  fn filename() -> &'static str {
+    "history.json"
+  }
+
+  /// Load the history stored in `dir`, starting from an empty one if there
+  /// is none yet.
+  pub(crate) fn load(dir: &Path) -> Result<Self> {
+    let path = dir.join(Self::filename());
+
+    let content = match fs::read_to_string(&path) {
+      Ok(content) => content,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        return Ok(Self::default())
+      }
+      Err(error) => return Err(error.into()),
+    };
+
+    serde_json::from_str(&content)
+      .map_err(|source| Error::HistoryInvalid { path, source })
+  }
+
+  pub(crate) fn save(&self, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let path = dir.join(Self::filename());
+    let temp = path.with_extension("json.tmp");
+
+    fs::write(&temp, serde_json::to_string(self)?)?;
+    fs::rename(&temp, &path)?;
+
+    Ok(())
+  }
+
+  /// Record that `command` made `changes` to the vault in `root`, which can
+  /// no longer be redone on top of anything undone before.
+  pub(crate) fn push(
+    &mut self,
+    root: &Path,
+    command: &str,
+    changes: Vec<Change>,
+  ) {
+    self.done.push(Operation {
+      command: command.to_owned(),
+      time: timestamp::now(),
+      changes: changes
+        .into_iter()
+        .map(|change| Change {
+          path: change
+            .path
+            .strip_prefix(root)
+            .map(Path::to_owned)
+            .unwrap_or(change.path),
+          ..change
+        })
+        .collect(),
+    });
+
+    let mut size = self.done.iter().map(Operation::size).sum::<usize>();
+
+    while self.done.len() > Self::LIMIT || size > Self::SIZE_LIMIT {
+      size -= self.done.remove(0).size();
+    }
+
+    self.undone.clear();
+  }
+
+  /// Drop every command that changed a file within `dir`, relative to the
+  /// vault, along with the commands that can only be undone or redone after
+  /// it, so that nothing of what was in `dir` is kept.
+  pub(crate) fn forget(&mut self, dir: &Path) {
+    for operations in [&mut self.done, &mut self.undone] {
+      if let Some(last) = operations
+        .iter()
+        .rposition(|operation| operation.touches(dir))
+      {
+        operations.drain(..=last);
+      }
+    }
+  }
+
+  /// Revert the most recent command.
+  pub(crate) fn undo(&mut self, root: &Path) -> Result<Operation> {
+    let operation = self.done.pop().ok_or(Error::UndoEmpty)?;
+
+    if let Err(error) = Self::apply(root, &operation, false) {
+      self.done.push(operation);
+      return Err(error);
+    }
+
+    self.undone.push(operation.clone());
+
+    Ok(operation)
+  }
+
+  /// Replay the most recently undone command.
+  pub(crate) fn redo(&mut self, root: &Path) -> Result<Operation> {
+    let operation = self.undone.pop().ok_or(Error::RedoEmpty)?;
+
+    if let Err(error) = Self::apply(root, &operation, true) {
+      self.undone.push(operation);
+      return Err(error);
+    }
+
+    self.done.push(operation.clone());
+
+    Ok(operation)
+  }
+
+  /// Bring every file `operation` changed to its contents after the command,
+  /// or before it, refusing to when a file has been changed since.
+  fn apply(root: &Path, operation: &Operation, forward: bool) -> Result<()> {
+    let changes = operation
+      .changes
+      .iter()
+      .map(|change| {
+        let (from, to) = if forward {
+          (&change.before, &change.after)
+        } else {
+          (&change.after, &change.before)
+        };
+
+        (root.join(&change.path), from, to)
+      })
+      .collect::<Vec<_>>();
+
+    for (path, from, _) in &changes {
+      if fs::read_to_string(path).ok() != **from {
+        return Err(Error::HistoryConflict {
+          path: path.to_owned(),
+        });
+      }
+    }
+
+    store::transaction(|| {
+      for (path, _, to) in &changes {
+        match to {
+          Some(contents) => store::write(path, contents)?,
+          None => store::remove(path)?,
+        }
+      }
+
+      Ok(())
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn undo_and_redo() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let mut a = create_note("a").unwrap();
+      let b = create_note("b").unwrap();
+
+      let before = (
+        fs::read_to_string(&a.path).unwrap(),
+        fs::read_to_string(&b.path).unwrap(),
+      );
+
+      let (result, changes) = store::record(|| {
+        a.add_link(&b.id.to_string(), &Names::default())?;
+        b.remove()
+      });
+
+      result.unwrap();
+
+      let after = fs::read_to_string(&a.path).unwrap();
+
+      let mut history = History::default();
+
+      history.push(&root, "link a b", changes);
+
+      assert_eq!(
+        history.done[0]
+          .changes
+          .iter()
+          .map(|change| change.path.clone())
+          .collect::<Vec<_>>(),
+        vec![
+          PathBuf::from(a.id.to_string()),
+          PathBuf::from(b.id.to_string())
+        ]
+      );
+
+      let operation = history.undo(&root).unwrap();
+
+      assert_eq!(operation.to_string(), "`zk link a b`");
+      assert_eq!(fs::read_to_string(&a.path).unwrap(), before.0);
+      assert_eq!(fs::read_to_string(&b.path).unwrap(), before.1);
+      assert!(matches!(history.undo(&root), Err(Error::UndoEmpty)));
+
+      history.redo(&root).unwrap();
+
+      assert_eq!(fs::read_to_string(&a.path).unwrap(), after);
+      assert!(!b.path.exists());
+      assert!(matches!(history.redo(&root), Err(Error::RedoEmpty)));
+
+      history.save(&root).unwrap();
+
+      let mut history = History::load(&root).unwrap();
+
+      history.undo(&root).unwrap();
+      history.push(&root, "tag a software", Vec::new());
+
+      assert!(history.undone.is_empty());
+    });
+  }
+
+  #[test]
+  fn conflict() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let mut a = create_note("a").unwrap();
+
+      let (result, changes) = store::record(|| a.add_tag("software"));
+
+      result.unwrap();
+
+      let mut history = History::default();
+
+      history.push(&root, "tag a software", changes);
+
+      fs::write(&a.path, "edited").unwrap();
+
+      assert!(matches!(
+        history.undo(&root),
+        Err(Error::HistoryConflict { .. })
+      ));
+
+      assert_eq!(history.done.len(), 1);
+      assert_eq!(fs::read_to_string(&a.path).unwrap(), "edited");
+    });
+  }
+
+  #[test]
+  fn load_invalid() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      assert!(History::load(&root).unwrap().done.is_empty());
+
+      fs::write(root.join(History::filename()), "{").unwrap();
+
+      assert!(matches!(
+        History::load(&root),
+        Err(Error::HistoryInvalid { .. })
+      ));
+    });
+  }
+
+  #[test]
+  fn limits() {
+    let mut history = History::default();
+
+    let change = |path: &str, size: usize| Change {
+      path: path.into(),
+      before: None,
+      after: Some("a".repeat(size)),
+    };
+
+    for i in 0..History::LIMIT + 10 {
+      history.push(Path::new(""), &i.to_string(), vec![change("a", 1)]);
+    }
+
+    assert_eq!(history.done.len(), History::LIMIT);
+    assert_eq!(history.done[0].command, "10");
+
+    history.push(
+      Path::new(""),
+      "large",
+      vec![change("a", History::SIZE_LIMIT / 2)],
+    );
+    history.push(
+      Path::new(""),
+      "larger",
+      vec![change("b", History::SIZE_LIMIT / 2)],
+    );
+
+    assert_eq!(
+      history
+        .done
+        .iter()
+        .map(|operation| operation.command.as_str())
+        .collect::<Vec<_>>(),
+      ["larger"]
+    );
+  }
+
+  #[test]
+  fn forget() {
+    let mut history = History::default();
+
+    for (command, path) in [
+      ("rm a", ".zk/trash/a/a.md"),
+      ("rm b", ".zk/trash/b/b.md"),
+      ("tag c", "c.md"),
+    ] {
+      history.push(
+        Path::new(""),
+        command,
+        vec![Change {
+          path: path.into(),
+          before: None,
+          after: Some(String::new()),
+        }],
+      );
+    }
+
+    history.forget(Path::new(".zk/trash/a"));
+
+    assert_eq!(history.done.len(), 2);
+
+    history.forget(Path::new(".zk/trash/b"));
+
+    assert_eq!(history.done.len(), 1);
+    assert_eq!(history.done[0].command, "tag c");
+  }
+}
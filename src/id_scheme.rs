@@ -0,0 +1,229 @@
+use {
+  crate::common::*,
+  chrono::{Duration, NaiveDateTime},
+  rand::Rng,
+  ulid::Ulid,
+};
+
+/// How the prefix of a new note's id is generated.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum IdScheme {
+  /// Eight random lowercase letters and digits, such as `k3x9q2ab`
+  Base36,
+  /// The creation minute, written as `YYYYMMDDHHMM`
+  Datetime,
+  /// Milliseconds since the Unix epoch
+  Millis,
+  /// A ULID, such as `01F9H1N5M8Q7VJ7Y2G6K3V4B5C`
+  Ulid,
+  /// Seconds since the Unix epoch
+  #[default]
+  Unix,
+}
+
+impl IdScheme {
+  const BASE36_ALPHABET: &'static [u8] =
+    b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+  const BASE36_LENGTH: usize = 8;
+
+  const DATETIME_FORMAT: &'static str = "%Y%m%d%H%M";
+
+  /// Every scheme, in the order prefixes are matched against them.
+  const DETECTION_ORDER: &'static [IdScheme] = &[
+    IdScheme::Datetime,
+    IdScheme::Millis,
+    IdScheme::Unix,
+    IdScheme::Ulid,
+    IdScheme::Base36,
+  ];
+
+  /// A prefix for a note created at `time` that `taken` does not reject.
+  ///
+  /// Time based schemes move forward by their resolution until they find a
+  /// free prefix, and random ones simply draw again.
+  pub(crate) fn generate(
+    self,
+    time: DateTime<Utc>,
+    taken: impl Fn(&str) -> bool,
+  ) -> String {
+    let mut time = time;
+
+    loop {
+      let prefix = self.format(time);
+
+      if !taken(&prefix) {
+        return prefix;
+      }
+
+      time += self.resolution();
+    }
+  }
+
+  /// The scheme that generated `prefix`, if any.
+  pub(crate) fn detect(prefix: &str) -> Option<Self> {
+    Self::DETECTION_ORDER
+      .iter()
+      .copied()
+      .find(|scheme| scheme.matches(prefix))
+  }
+
+  /// Whether `prefix` could have been generated by this scheme.
+  pub(crate) fn matches(self, prefix: &str) -> bool {
+    let digits =
+      !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit());
+
+    match self {
+      IdScheme::Base36 => {
+        prefix.len() == Self::BASE36_LENGTH
+          && prefix.bytes().all(|b| Self::BASE36_ALPHABET.contains(&b))
+      }
+      IdScheme::Datetime => {
+        digits
+          && prefix.len() == 12
+          && NaiveDateTime::parse_from_str(
+            &format!("{}00", prefix),
+            "%Y%m%d%H%M%S",
+          )
+          .is_ok()
+      }
+      IdScheme::Millis => digits && prefix.len() == 13,
+      IdScheme::Ulid => prefix.len() == 26 && Ulid::from_string(prefix).is_ok(),
+      IdScheme::Unix => digits && prefix.len() <= 11,
+    }
+  }
+
+  /// When a note whose prefix was generated by this scheme was created.
+  pub(crate) fn created(self, prefix: &str) -> Option<DateTime<Utc>> {
+    if !self.matches(prefix) {
+      return None;
+    }
+
+    match self {
+      IdScheme::Base36 => None,
+      IdScheme::Datetime => Some(
+        NaiveDateTime::parse_from_str(&format!("{}00", prefix), "%Y%m%d%H%M%S")
+          .ok()?
+          .and_utc(),
+      ),
+      IdScheme::Millis => DateTime::from_timestamp_millis(prefix.parse().ok()?),
+      IdScheme::Ulid => {
+        Some(DateTime::from(Ulid::from_string(prefix).ok()?.datetime()))
+      }
+      IdScheme::Unix => DateTime::from_timestamp(prefix.parse().ok()?, 0),
+    }
+  }
+
+  fn format(self, time: DateTime<Utc>) -> String {
+    match self {
+      IdScheme::Base36 => {
+        let mut rng = rand::rng();
+
+        (0..Self::BASE36_LENGTH)
+          .map(|_| {
+            Self::BASE36_ALPHABET
+              [rng.random_range(0..Self::BASE36_ALPHABET.len())]
+              as char
+          })
+          .collect()
+      }
+      IdScheme::Datetime => time.format(Self::DATETIME_FORMAT).to_string(),
+      IdScheme::Millis => time.timestamp_millis().to_string(),
+      IdScheme::Ulid => Ulid::from_datetime(SystemTime::from(time)).to_string(),
+      IdScheme::Unix => time.timestamp().to_string(),
+    }
+  }
+
+  fn resolution(self) -> Duration {
+    match self {
+      IdScheme::Datetime => Duration::minutes(1),
+      IdScheme::Unix => Duration::seconds(1),
+      IdScheme::Base36 | IdScheme::Millis | IdScheme::Ulid => {
+        Duration::milliseconds(1)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn time() -> DateTime<Utc> {
+    timestamp::parse("2021-07-01T01:51:09Z").unwrap()
+  }
+
+  const ALL: &[IdScheme] = &[
+    IdScheme::Base36,
+    IdScheme::Datetime,
+    IdScheme::Millis,
+    IdScheme::Ulid,
+    IdScheme::Unix,
+  ];
+
+  #[test]
+  fn generate() {
+    let never = |_: &str| false;
+
+    assert_eq!(IdScheme::Unix.generate(time(), never), "1625104269");
+    assert_eq!(IdScheme::Millis.generate(time(), never), "1625104269000");
+    assert_eq!(IdScheme::Datetime.generate(time(), never), "202107010151");
+    assert_eq!(IdScheme::Ulid.generate(time(), never).len(), 26);
+    assert_eq!(IdScheme::Base36.generate(time(), never).len(), 8);
+  }
+
+  #[test]
+  fn generate_skips_taken_prefixes() {
+    for &scheme in ALL {
+      let mut taken = BTreeSet::new();
+
+      for _ in 0..10 {
+        let prefix = scheme.generate(time(), |prefix| taken.contains(prefix));
+        assert!(taken.insert(prefix), "{:?}", scheme);
+      }
+    }
+
+    assert_eq!(
+      IdScheme::Datetime.generate(time(), |prefix| prefix == "202107010151"),
+      "202107010152"
+    );
+  }
+
+  #[test]
+  fn detect() {
+    for &scheme in ALL {
+      let prefix = scheme.generate(time(), |_| false);
+
+      // a random base36 prefix may happen to be all digits
+      if scheme == IdScheme::Base36
+        && prefix.bytes().all(|b| b.is_ascii_digit())
+      {
+        continue;
+      }
+
+      assert_eq!(IdScheme::detect(&prefix), Some(scheme), "{}", prefix);
+    }
+
+    assert_eq!(IdScheme::detect("a"), None);
+    assert_eq!(IdScheme::detect(""), None);
+    assert_eq!(IdScheme::detect("202113010151"), None);
+  }
+
+  #[test]
+  fn created() {
+    for &scheme in ALL {
+      let prefix = scheme.generate(time(), |_| false);
+
+      let want = match scheme {
+        IdScheme::Base36 => None,
+        IdScheme::Datetime => timestamp::parse("2021-07-01T01:51:00Z"),
+        _ => Some(time()),
+      };
+
+      assert_eq!(scheme.created(&prefix), want, "{:?}", scheme);
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+  modified: SystemTime,
+  size: u64,
+  matter: Matter,
+  frontmatter: String,
+  content: String,
+}
+
+impl Entry {
+  fn is_fresh(&self, metadata: &fs::Metadata) -> Result<bool> {
+    Ok(self.size == metadata.len() && self.modified == metadata.modified()?)
+  }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Index {
+  version: u32,
+  entries: BTreeMap<PathBuf, Entry>,
+  #[serde(skip)]
+  dirty: bool,
+}
+
+impl Index {
+  const VERSION: u32 = 4;
+
+  fn filename() -> &'static str {
+    "index.json"
+  }
+
+  /// Load the index stored in `dir`, starting from an empty one if it is
+  /// missing, unreadable or was written by an incompatible version.
+  pub(crate) fn load(dir: &Path) -> Self {
+    fs::read_to_string(dir.join(Self::filename()))
+      .ok()
+      .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+      .filter(|index| index.version == Self::VERSION)
+      .unwrap_or(Self {
+        version: Self::VERSION,
+        entries: BTreeMap::new(),
+        dirty: true,
+      })
+  }
+
+  /// Bring the index up to date with `paths`, re-parsing only the notes whose
+  /// size or modification time changed since they were last seen.
+  pub(crate) fn refresh(
+    &mut self,
+    root: &Path,
+    paths: Vec<PathBuf>,
+  ) -> Result<Vec<Note>> {
+    let mut seen = BTreeSet::new();
+
+    let notes = paths
+      .into_iter()
+      .map(|path| {
+        let key = path.strip_prefix(root).unwrap_or(&path).to_owned();
+
+        let metadata = fs::metadata(&path)?;
+
+        let entry = match self.entries.get(&key) {
+          Some(entry) if entry.is_fresh(&metadata)? => entry.clone(),
+          _ => {
+            let note = Note::from(path.clone())?;
+
+            let entry = Entry {
+              modified: metadata.modified()?,
+              size: metadata.len(),
+              frontmatter: note.matter.source.clone(),
+              matter: note.matter,
+              content: note.content,
+            };
+
+            self.entries.insert(key.clone(), entry.clone());
+            self.dirty = true;
+
+            entry
+          }
+        };
+
+        seen.insert(key);
+
+        Note::new(
+          path,
+          Matter {
+            source: entry.frontmatter,
+            ..entry.matter
+          },
+          entry.content,
+        )
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    let before = self.entries.len();
+    self.entries.retain(|key, _| seen.contains(key));
+    self.dirty |= self.entries.len() != before;
+
+    Ok(notes)
+  }
+
+  /// Persist the index into `dir` if anything changed since it was loaded.
+  pub(crate) fn save(&mut self, dir: &Path) -> Result<()> {
+    if !self.dirty {
+      return Ok(());
+    }
+
+    store::replace(&dir.join(Self::filename()), &serde_json::to_string(self)?)?;
+
+    self.dirty = false;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .filter(|path| path.is_file())
+      .collect::<Vec<_>>();
+    paths.sort();
+    paths
+  }
+
+  #[test]
+  fn refresh_reuses_fresh_entries() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      create_note("a").unwrap();
+      create_note("b").unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      assert_eq!(index.refresh(&root, paths(&root)).unwrap().len(), 2);
+      index.save(&root.join(".zk")).unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      assert!(!index.dirty);
+      assert_eq!(index.refresh(&root, paths(&root)).unwrap().len(), 2);
+      assert!(!index.dirty);
+    });
+  }
+
+  #[test]
+  fn refresh_reparses_changed_notes() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let mut a = create_note("a").unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      index.refresh(&root, paths(&root)).unwrap();
+      index.save(&root.join(".zk")).unwrap();
+
+      a.add_tag("software").unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      let notes = index.refresh(&root, paths(&root)).unwrap();
+
+      assert!(index.dirty);
+      assert!(notes[0].has_tag("software"));
+    });
+  }
+
+  #[test]
+  fn refresh_drops_removed_notes() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let a = create_note("a").unwrap();
+      create_note("b").unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      index.refresh(&root, paths(&root)).unwrap();
+      index.save(&root.join(".zk")).unwrap();
+
+      a.remove().unwrap();
+
+      let mut index = Index::load(&root.join(".zk"));
+      assert_eq!(index.refresh(&root, paths(&root)).unwrap().len(), 1);
+      assert_eq!(index.entries.len(), 1);
+      assert!(index.dirty);
+    });
+  }
+
+  #[test]
+  fn load_ignores_corrupt_index() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir(root.join(".zk")).unwrap();
+      fs::write(root.join(".zk").join(Index::filename()), "{").unwrap();
+
+      let index = Index::load(&root.join(".zk"));
+      assert!(index.entries.is_empty());
+      assert!(index.dirty);
+    });
+  }
+}
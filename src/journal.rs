@@ -0,0 +1,157 @@
+use {
+  crate::common::*,
+  chrono::{Days, NaiveDate, Weekday},
+};
+
+/// How journal notes are named and which templates they start from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Journal {
+  /// `strftime` pattern for daily note names
+  pub(crate) daily: String,
+  /// `strftime` pattern for weekly note names, given the week's Monday
+  pub(crate) weekly: String,
+  pub(crate) daily_template: Option<String>,
+  pub(crate) weekly_template: Option<String>,
+}
+
+impl Default for Journal {
+  fn default() -> Self {
+    Self {
+      daily: "%Y-%m-%d".into(),
+      weekly: "%G-W%V".into(),
+      daily_template: None,
+      weekly_template: None,
+    }
+  }
+}
+
+/// The span of time a single journal note covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Period {
+  Day(NaiveDate),
+  /// A week, starting on its Monday
+  Week(NaiveDate),
+}
+
+impl Period {
+  /// How many periods to look back for the previous journal note.
+  pub(crate) const LOOKBACK: usize = 366;
+
+  pub(crate) fn today() -> Self {
+    Period::Day(Local::now().date_naive())
+  }
+
+  pub(crate) fn yesterday() -> Self {
+    Period::today().previous()
+  }
+
+  pub(crate) fn week() -> Self {
+    Period::Week(Local::now().date_naive().week(Weekday::Mon).first_day())
+  }
+
+  /// Parse a day given as `YYYY-MM-DD`, `today` or `yesterday`.
+  pub(crate) fn parse(text: &str) -> Result<Self> {
+    match text {
+      "today" => Ok(Period::today()),
+      "yesterday" => Ok(Period::yesterday()),
+      _ => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map(Period::Day)
+        .map_err(|_| Error::DateInvalid {
+          text: text.to_owned(),
+        }),
+    }
+  }
+
+  /// The first day of the period.
+  pub(crate) fn date(self) -> NaiveDate {
+    match self {
+      Period::Day(date) | Period::Week(date) => date,
+    }
+  }
+
+  pub(crate) fn previous(self) -> Self {
+    match self {
+      Period::Day(date) => Period::Day(date - Days::new(1)),
+      Period::Week(date) => Period::Week(date - Days::new(7)),
+    }
+  }
+
+  /// Every earlier period, most recent first.
+  pub(crate) fn before(self) -> impl Iterator<Item = Self> {
+    iter::successors(Some(self.previous()), |period| Some(period.previous()))
+  }
+
+  pub(crate) fn name(self, journal: &Journal) -> String {
+    let pattern = match self {
+      Period::Day(_) => &journal.daily,
+      Period::Week(_) => &journal.weekly,
+    };
+
+    self.date().format(pattern).to_string()
+  }
+
+  pub(crate) fn template(self, journal: &Journal) -> Option<&str> {
+    match self {
+      Period::Day(_) => journal.daily_template.as_deref(),
+      Period::Week(_) => journal.weekly_template.as_deref(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, chrono::Datelike};
+
+  fn date(text: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+  }
+
+  #[test]
+  fn names() {
+    let journal = Journal::default();
+
+    assert_eq!(Period::Day(date("2021-07-01")).name(&journal), "2021-07-01");
+    assert_eq!(Period::Week(date("2021-06-28")).name(&journal), "2021-W26");
+
+    let journal = Journal {
+      daily: "journal-%Y%m%d".into(),
+      ..Journal::default()
+    };
+
+    assert_eq!(
+      Period::Day(date("2021-07-01")).name(&journal),
+      "journal-20210701"
+    );
+  }
+
+  #[test]
+  fn previous() {
+    assert_eq!(
+      Period::Day(date("2021-07-01")).previous(),
+      Period::Day(date("2021-06-30"))
+    );
+
+    assert_eq!(
+      Period::Week(date("2021-06-28")).previous(),
+      Period::Week(date("2021-06-21"))
+    );
+
+    assert_eq!(Period::week().date().weekday(), Weekday::Mon);
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      Period::parse("2021-07-01").unwrap(),
+      Period::Day(date("2021-07-01"))
+    );
+    assert_eq!(Period::parse("today").unwrap(), Period::today());
+    assert_eq!(
+      Period::parse("yesterday").unwrap(),
+      Period::today().previous()
+    );
+    assert!(Period::parse("2021-13-01").is_err());
+    assert!(Period::parse("tomorrow").is_err());
+  }
+}
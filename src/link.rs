@@ -0,0 +1,420 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinkKind {
+  /// A note filename listed under `links` in the frontmatter
+  Matter,
+  /// An inline `[[target]]` or `[[target|alias]]` link
+  Wiki,
+  /// An inline `[text](target.md)` link
+  Markdown,
+}
+
+/// How many notes in a vault have each name. A wikilink giving only a name,
+/// such as `[[a]]`, resolves only when exactly one note is called that, so
+/// that moving or removing one of several notes named `a` leaves the links
+/// meant for the others alone.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Names(BTreeMap<String, usize>);
+
+impl Names {
+  pub(crate) fn new<'a>(ids: impl IntoIterator<Item = &'a NoteId>) -> Self {
+    let mut names = BTreeMap::new();
+
+    for id in ids {
+      *names.entry(id.name.clone()).or_default() += 1;
+    }
+
+    Self(names)
+  }
+
+  /// How many notes are called `name`.
+  pub(crate) fn count(&self, name: &str) -> usize {
+    self.0.get(name).copied().unwrap_or_default()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Link {
+  pub(crate) kind: LinkKind,
+  pub(crate) target: String,
+  pub(crate) text: Option<String>,
+}
+
+impl Link {
+  pub(crate) fn matter(target: &str) -> Self {
+    Self {
+      kind: LinkKind::Matter,
+      target: target.to_owned(),
+      text: None,
+    }
+  }
+
+  /// Every inline link in a note body, in the order they appear.
+  pub(crate) fn parse(content: &str) -> Vec<Self> {
+    spans(content).into_iter().map(|(_, link)| link).collect()
+  }
+
+  /// Whether this link refers to the note with `id`, in a vault with the
+  /// note `names`.
+  ///
+  /// Frontmatter and markdown links name the note's filename, while wikilinks
+  /// may use the filename, the filename without its extension or only the
+  /// note name, as long as no other note has that name.
+  pub(crate) fn points_to(&self, id: &NoteId, names: &Names) -> bool {
+    match self.kind {
+      LinkKind::Matter | LinkKind::Markdown => self
+        .target
+        .strip_suffix(".md")
+        .is_some_and(|stem| id.is_stem(stem)),
+      LinkKind::Wiki => {
+        let target = self.target.strip_suffix(".md").unwrap_or(&self.target);
+        id.is_stem(target) || (target == id.name && names.count(target) == 1)
+      }
+    }
+  }
+
+  /// The vault name and the link within that vault, for a cross-vault link
+  /// such as `work:1625104269-a`.
+  pub(crate) fn remote(&self) -> Option<(&str, Link)> {
+    let (vault, target) = self.target.split_once(':')?;
+
+    if vault.is_empty() || target.is_empty() {
+      return None;
+    }
+
+    Some((
+      vault,
+      Link {
+        target: target.to_owned(),
+        ..self.clone()
+      },
+    ))
+  }
+
+  /// The target this link would have if it pointed to `new` instead of `old`,
+  /// written in the same form as the current one.
+  fn retarget(&self, old: &NoteId, new: &NoteId) -> String {
+    match self.kind {
+      LinkKind::Matter | LinkKind::Markdown => new.to_string(),
+      LinkKind::Wiki => match self.target.strip_suffix(".md") {
+        Some(_) => new.to_string(),
+        None if old.is_stem(&self.target) => new.stem(),
+        None => new.name.clone(),
+      },
+    }
+  }
+
+  /// The text a link is replaced with when it is removed from a note body.
+  fn plain(&self) -> &str {
+    self.text.as_deref().unwrap_or(&self.target)
+  }
+}
+
+/// Replace every inline link in `content` that points to `id` with its plain
+/// text, leaving the surrounding prose intact.
+pub(crate) fn unlink(content: &str, id: &NoteId, names: &Names) -> String {
+  let mut result = String::with_capacity(content.len());
+  let mut last = 0;
+
+  for (range, link) in spans(content) {
+    if link.points_to(id, names) {
+      result.push_str(&content[last..range.start]);
+      result.push_str(link.plain());
+      last = range.end;
+    }
+  }
+
+  result.push_str(&content[last..]);
+
+  result
+}
+
+/// Rewrite every inline link in `content` that points to `old` so that it
+/// points to `new`, keeping aliases, anchors and link text.
+pub(crate) fn relink(
+  content: &str,
+  old: &NoteId,
+  new: &NoteId,
+  names: &Names,
+) -> String {
+  let mut result = String::with_capacity(content.len());
+  let mut last = 0;
+
+  for (range, link) in spans(content) {
+    if !link.points_to(old, names) {
+      continue;
+    }
+
+    let original = &content[range.clone()];
+
+    let start = match link.kind {
+      LinkKind::Markdown => original.find("](").map_or(0, |i| i + 2),
+      _ => 2,
+    };
+
+    let target = vec![link.target.clone(), link.target.replace(' ', "%20")]
+      .into_iter()
+      .find(|target| original[start..].contains(target.as_str()));
+
+    if let Some(target) = target {
+      result.push_str(&content[last..range.start]);
+      result.push_str(&original[..start]);
+      result.push_str(&original[start..].replacen(
+        target.as_str(),
+        &link.retarget(old, new),
+        1,
+      ));
+      last = range.end;
+    }
+  }
+
+  result.push_str(&content[last..]);
+
+  result
+}
+
+/// Find inline links along with their byte ranges, skipping fenced code
+/// blocks and inline code spans.
+fn spans(content: &str) -> Vec<(Range<usize>, Link)> {
+  let mut spans = Vec::new();
+  let mut fenced = false;
+  let mut offset = 0;
+
+  for line in content.split_inclusive('\n') {
+    if line.trim_start().starts_with("```") {
+      fenced = !fenced;
+    } else if !fenced {
+      spans.extend(
+        line_spans(line).into_iter().map(|(range, link)| {
+          (range.start + offset..range.end + offset, link)
+        }),
+      );
+    }
+
+    offset += line.len();
+  }
+
+  spans
+}
+
+fn line_spans(line: &str) -> Vec<(Range<usize>, Link)> {
+  let mut spans = Vec::new();
+  let mut i = 0;
+
+  while i < line.len() {
+    let rest = &line[i..];
+
+    if rest.starts_with('`') {
+      let ticks = rest.len() - rest.trim_start_matches('`').len();
+      match rest[ticks..].find(&rest[..ticks]) {
+        Some(end) => i += ticks + end + ticks,
+        None => i += ticks,
+      }
+      continue;
+    }
+
+    if rest.starts_with("[[") {
+      if let Some(end) = rest.find("]]") {
+        if let Some(link) = wikilink(&rest[2..end]) {
+          spans.push((i..i + end + 2, link));
+        }
+        i += end + 2;
+        continue;
+      }
+    }
+
+    if rest.starts_with('[') && !line[..i].ends_with('!') {
+      if let Some((len, link)) = markdown(rest) {
+        spans.push((i..i + len, link));
+        i += len;
+        continue;
+      }
+    }
+
+    i += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+  }
+
+  spans
+}
+
+fn wikilink(inner: &str) -> Option<Link> {
+  let (target, text) = match inner.split_once('|') {
+    Some((target, text)) => (target, Some(text.trim().to_owned())),
+    None => (inner, None),
+  };
+
+  let target = target.split('#').next().unwrap_or_default().trim();
+
+  if target.is_empty() || target.contains('[') {
+    return None;
+  }
+
+  Some(Link {
+    kind: LinkKind::Wiki,
+    target: target.to_owned(),
+    text,
+  })
+}
+
+fn markdown(rest: &str) -> Option<(usize, Link)> {
+  let close = rest.find("](")?;
+  let text = &rest[1..close];
+
+  if text.contains('[') || text.contains(']') {
+    return None;
+  }
+
+  let end = close + 2 + rest[close + 2..].find(')')?;
+
+  let destination = rest[close + 2..end]
+    .split_whitespace()
+    .next()
+    .unwrap_or_default();
+
+  let destination = destination.split('#').next().unwrap_or_default();
+
+  if destination.contains("://") || !destination.ends_with(".md") {
+    return None;
+  }
+
+  let target = PathBuf::from(destination.replace("%20", " "))
+    .unwrapped_filename()
+    .to_owned();
+
+  Some((
+    end + 1,
+    Link {
+      kind: LinkKind::Markdown,
+      target,
+      text: Some(text.to_owned()),
+    },
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn wiki(target: &str, text: Option<&str>) -> Link {
+    Link {
+      kind: LinkKind::Wiki,
+      target: target.into(),
+      text: text.map(String::from),
+    }
+  }
+
+  fn markdown(target: &str, text: &str) -> Link {
+    Link {
+      kind: LinkKind::Markdown,
+      target: target.into(),
+      text: Some(text.into()),
+    }
+  }
+
+  #[test]
+  fn parse() {
+    let cases = vec![
+      ("[[a]]", vec![wiki("a", None)]),
+      ("see [[1625104269-a]].", vec![wiki("1625104269-a", None)]),
+      ("[[a|the a note]]", vec![wiki("a", Some("the a note"))]),
+      ("[[a#heading]]", vec![wiki("a", None)]),
+      (
+        "[b](1625104326-b.md) and [c](./1625104344-c.md#top)",
+        vec![
+          markdown("1625104326-b.md", "b"),
+          markdown("1625104344-c.md", "c"),
+        ],
+      ),
+      (
+        "[d](1625104359-d.md \"title\")",
+        vec![markdown("1625104359-d.md", "d")],
+      ),
+      (
+        "[e](1625104364%20e.md)",
+        vec![markdown("1625104364 e.md", "e")],
+      ),
+      ("[site](https://example.com/a.md)", vec![]),
+      ("[image](image.png)", vec![]),
+      ("![a](1625104269-a.md)", vec![]),
+      ("`[[a]]` and ``[b](b.md)``", vec![]),
+      ("```\n[[a]]\n```\n[[b]]", vec![wiki("b", None)]),
+      ("[[]] [[a", vec![]),
+    ];
+
+    for (content, want) in cases {
+      assert_eq!(Link::parse(content), want, "{}", content);
+    }
+  }
+
+  fn names(filenames: &[&str]) -> Names {
+    Names::new(
+      filenames
+        .iter()
+        .map(|filename| NoteId::parse(filename).unwrap())
+        .collect::<Vec<_>>()
+        .iter(),
+    )
+  }
+
+  #[test]
+  fn points_to() {
+    let id = NoteId::parse("1625104269-a.md").unwrap();
+    let names = names(&["1625104269-a.md", "1625104326-b.md"]);
+
+    assert!(Link::matter("1625104269-a.md").points_to(&id, &names));
+    assert!(!Link::matter("a").points_to(&id, &names));
+    assert!(wiki("a", None).points_to(&id, &names));
+    assert!(wiki("1625104269-a", None).points_to(&id, &names));
+    assert!(wiki("1625104269-a.md", None).points_to(&id, &names));
+    assert!(!wiki("b", None).points_to(&id, &names));
+    assert!(markdown("1625104269-a.md", "a").points_to(&id, &names));
+    assert!(!markdown("a.md", "a").points_to(&id, &names));
+  }
+
+  #[test]
+  fn points_to_ambiguous_name() {
+    let id = NoteId::parse("1625104269-a.md").unwrap();
+    let names = names(&["1625104269-a.md", "1625104344-a.md"]);
+
+    assert!(!wiki("a", None).points_to(&id, &names));
+    assert!(wiki("1625104269-a", None).points_to(&id, &names));
+
+    assert_eq!(
+      super::unlink("[[a]] and [[1625104269-a]]", &id, &names),
+      "[[a]] and 1625104269-a"
+    );
+  }
+
+  #[test]
+  fn relink() {
+    let old = NoteId::parse("1625104269-a.md").unwrap();
+    let new = NoteId::parse("1625104269-b.md").unwrap();
+
+    assert_eq!(
+      super::relink(
+        "[[a]], [[a|alias]], [[1625104269-a#top]], [[1625104269-a.md]], \
+         [text](./1625104269-a.md#top \"title\") and [[c]]",
+        &old,
+        &new,
+        &names(&["1625104269-a.md"])
+      ),
+      "[[b]], [[b|alias]], [[1625104269-b#top]], [[1625104269-b.md]], \
+       [text](./1625104269-b.md#top \"title\") and [[c]]"
+    );
+  }
+
+  #[test]
+  fn unlink() {
+    let id = NoteId::parse("1625104269-a.md").unwrap();
+
+    assert_eq!(
+      super::unlink(
+        "See [[a]], [[a|this]] and [there](1625104269-a.md), not [[b]].",
+        &id,
+        &names(&["1625104269-a.md"])
+      ),
+      "See a, this and there, not [[b]]."
+    );
+  }
+}
@@ -0,0 +1,60 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ListSort {
+  /// Oldest first
+  Created,
+  /// By filename
+  #[default]
+  Name,
+  /// Most recently updated first
+  Updated,
+}
+
+impl ListSort {
+  pub(crate) fn sort(self, mut listings: Vec<Listing>) -> Vec<Listing> {
+    match self {
+      ListSort::Created => listings.sort_by_key(|listing| listing.created),
+      ListSort::Name => {}
+      ListSort::Updated => {
+        listings.sort_by_key(|listing| Reverse(listing.updated))
+      }
+    }
+
+    listings
+  }
+}
+
+/// A note summary suitable for printing or handing to other tools.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Listing {
+  pub(crate) prefix: String,
+  pub(crate) name: String,
+  pub(crate) title: Option<String>,
+  pub(crate) path: PathBuf,
+  pub(crate) tags: Vec<String>,
+  pub(crate) links: Vec<String>,
+  pub(crate) backlinks: Vec<String>,
+  pub(crate) created: Option<DateTime<Utc>>,
+  pub(crate) updated: Option<DateTime<Utc>>,
+}
+
+impl Listing {
+  pub(crate) fn new(
+    note: &Note,
+    links: Vec<String>,
+    backlinks: Vec<String>,
+  ) -> Self {
+    Self {
+      prefix: note.id.prefix.clone(),
+      name: note.id.name.clone(),
+      title: note.matter.title.clone(),
+      path: note.path.clone(),
+      tags: note.matter.tags.clone().unwrap_or_default(),
+      links,
+      backlinks,
+      created: note.created(),
+      updated: note.matter.updated,
+    }
+  }
+}
@@ -6,15 +6,41 @@ mod test_utils;
 
 mod common;
 mod config;
+mod config_command;
 mod directory;
+mod doctor;
+mod editor;
 mod error;
+mod format;
 mod handler;
+mod history;
+mod id_scheme;
+mod index;
+mod journal;
+mod link;
+mod listing;
 mod matter;
+mod new_note;
 mod note;
 mod note_id;
 mod opt;
 mod path_ext;
+mod query;
+mod retag_command;
 mod search;
+mod selection;
+mod sequence;
+mod store;
+mod subcommand;
+mod tag_ext;
+mod tag_summary;
+mod tag_tree;
+mod template;
+mod text_index;
+mod timestamp;
+mod trash;
+mod trash_command;
+mod tree;
 
 fn main() {
   if let Err(error) = Opt::parse().run() {
@@ -1,10 +1,47 @@
 use crate::common::*;
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Eq, Clone, Serialize, Deserialize)]
 pub(crate) struct Matter {
   pub(crate) name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) title: Option<String>,
   pub(crate) tags: Option<Vec<String>>,
   pub(crate) links: Option<Vec<String>>,
+  /// The note's Folgezettel position, such as `1a2`
+  #[serde(
+    default,
+    skip_serializing_if = "Option::is_none",
+    deserialize_with = "Sequence::deserialize_optional"
+  )]
+  pub(crate) sequence: Option<Sequence>,
+  #[serde(
+    default,
+    skip_serializing_if = "Option::is_none",
+    with = "timestamp"
+  )]
+  pub(crate) created: Option<DateTime<Utc>>,
+  #[serde(
+    default,
+    skip_serializing_if = "Option::is_none",
+    with = "timestamp"
+  )]
+  pub(crate) updated: Option<DateTime<Utc>>,
+  /// The frontmatter as it was read, so that keys `zk` doesn't know about,
+  /// their order, comments and formatting survive writing the note back.
+  #[serde(skip)]
+  pub(crate) source: String,
+}
+
+impl PartialEq for Matter {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name
+      && self.title == other.title
+      && self.tags == other.tags
+      && self.links == other.links
+      && self.sequence == other.sequence
+      && self.created == other.created
+      && self.updated == other.updated
+  }
 }
 
 impl Matter {
@@ -15,31 +52,267 @@ impl Matter {
   ) -> Self {
     Self {
       name: name.to_owned(),
+      title: None,
       tags,
       links,
+      sequence: None,
+      created: None,
+      updated: None,
+      source: String::new(),
     }
   }
 
-  pub(crate) fn default(name: &str) -> Result<Vec<u8>> {
-    Ok(
-      Self::into(Matter::new(name, None, None))?
-        .as_bytes()
-        .to_owned(),
-    )
+  /// The frontmatter of a new note called `name`, built on top of `source`,
+  /// which is empty unless the note comes from a template.
+  pub(crate) fn default(name: &str, source: &str) -> Result<String> {
+    let matter = if source.trim().is_empty() {
+      Matter::new(name, None, None)
+    } else {
+      let has_name = match serde_yaml::from_str(source)? {
+        serde_yaml::Value::Mapping(mapping) => {
+          mapping.contains_key(&serde_yaml::Value::from("name"))
+        }
+        _ => false,
+      };
+
+      if has_name {
+        Matter::from(source)?
+      } else {
+        Matter::from(&format!("name: ''\n{}", source))?
+      }
+    };
+
+    Self::into(Matter {
+      name: name.to_owned(),
+      created: Some(timestamp::now()),
+      ..matter
+    })
+  }
+
+  /// The `created` and `updated` values that aren't timestamps `zk` can read,
+  /// as `(key, value)` pairs.
+  pub(crate) fn invalid_timestamps(&self) -> Vec<(String, String)> {
+    ["created", "updated"]
+      .iter()
+      .filter_map(|key| {
+        let text = self.raw(key)?;
+
+        timestamp::parse(&text)
+          .is_none()
+          .then(|| (key.to_string(), text))
+      })
+      .collect()
+  }
+
+  /// The `sequence` value, if it isn't a sequence `zk` can read.
+  pub(crate) fn invalid_sequence(&self) -> Option<String> {
+    let text = self.raw("sequence")?;
+    Sequence::parse(&text).is_err().then_some(text)
+  }
+
+  /// The text of the value of `key` as written in the frontmatter, unless it
+  /// is missing or null.
+  fn raw(&self, key: &str) -> Option<String> {
+    let mapping = match serde_yaml::from_str(&self.source) {
+      Ok(serde_yaml::Value::Mapping(mapping)) => mapping,
+      _ => return None,
+    };
+
+    match mapping.get(&serde_yaml::Value::from(key))? {
+      serde_yaml::Value::Null => None,
+      serde_yaml::Value::String(text) => Some(text.clone()),
+      value => Some(
+        serde_yaml::to_string(value)
+          .ok()?
+          .trim_start_matches("---\n")
+          .trim()
+          .to_owned(),
+      ),
+    }
+  }
+
+  /// Split a note into its frontmatter, without the `---` delimiters, and its
+  /// body, leaving both untouched.
+  pub(crate) fn split(text: &str) -> (&str, &str) {
+    let rest = match text
+      .strip_prefix("---\n")
+      .or_else(|| text.strip_prefix("---\r\n"))
+    {
+      Some(rest) => rest,
+      None => return ("", text),
+    };
+
+    let mut offset = 0;
+
+    for line in rest.split_inclusive('\n') {
+      if line.trim_end() == "---" {
+        return (&rest[..offset], &rest[offset + line.len()..]);
+      }
+
+      offset += line.len();
+    }
+
+    ("", text)
   }
 
   pub(crate) fn from(content: &str) -> Result<Self> {
-    Ok(serde_yaml::from_str(
-      content
-        .strip_prefix("---\n")
-        .unwrap_or(content)
-        .strip_suffix("---\n")
-        .unwrap_or(content),
-    )?)
+    let source = content
+      .strip_prefix("---\n")
+      .unwrap_or(content)
+      .strip_suffix("---\n")
+      .unwrap_or(content);
+
+    Ok(Self {
+      source: source.to_owned(),
+      ..serde_yaml::from_str(source)?
+    })
   }
 
   pub(crate) fn into(matter: Matter) -> Result<String> {
-    Ok(format!("{}---\n", serde_yaml::to_string(&matter)?))
+    if matter.source.trim().is_empty() {
+      return Ok(format!("{}---\n", serde_yaml::to_string(&matter)?));
+    }
+
+    let original = serde_yaml::to_value(Matter::from(&matter.source)?)?;
+    let updated = serde_yaml::to_value(&matter)?;
+
+    let (original, updated) = match (original, updated) {
+      (
+        serde_yaml::Value::Mapping(original),
+        serde_yaml::Value::Mapping(updated),
+      ) => (original, updated),
+      _ => unreachable!(),
+    };
+
+    let mut blocks = Block::parse(&matter.source);
+
+    for (key, value) in updated {
+      let name = key.as_str().unwrap_or_default();
+
+      if original.get(&key) == Some(&value) {
+        continue;
+      }
+
+      let mut field = serde_yaml::Mapping::new();
+      field.insert(key.clone(), value.clone());
+
+      let text = serde_yaml::to_string(&field)?
+        .trim_start_matches("---\n")
+        .to_owned();
+
+      match blocks
+        .iter_mut()
+        .find(|block| block.key.as_deref() == Some(name))
+      {
+        Some(block) => block.text = text,
+        None if value.is_null() => {}
+        None => blocks.push(Block {
+          key: Some(name.to_owned()),
+          text,
+        }),
+      }
+    }
+
+    let mut yaml = blocks
+      .into_iter()
+      .map(|block| block.text)
+      .collect::<String>();
+
+    if !yaml.ends_with('\n') {
+      yaml.push('\n');
+    }
+
+    Ok(format!("---\n{}---\n", yaml))
+  }
+}
+
+/// A run of frontmatter lines: either a top-level key together with its
+/// nested value, or lines such as comments that belong to no key.
+///
+/// Comments within a value stay with its key, while comments right before the
+/// next key belong to no key, so they survive the value being rewritten.
+#[derive(Debug, PartialEq, Eq)]
+struct Block {
+  key: Option<String>,
+  text: String,
+}
+
+impl Block {
+  fn parse(source: &str) -> Vec<Self> {
+    let mut blocks = Vec::<Self>::new();
+
+    for line in source.split_inclusive('\n') {
+      let key = Self::key(line);
+
+      if key.is_some() {
+        Self::detach_comments(&mut blocks);
+      }
+
+      match blocks.last_mut() {
+        Some(block) if key.is_none() => block.text.push_str(line),
+        _ => blocks.push(Block {
+          key,
+          text: line.to_owned(),
+        }),
+      }
+    }
+
+    blocks
+  }
+
+  /// Move the comments ending the last keyed block into a block of their own.
+  fn detach_comments(blocks: &mut Vec<Self>) {
+    let block = match blocks.last_mut() {
+      Some(block) if block.key.is_some() => block,
+      _ => return,
+    };
+
+    let lines = block.text.split_inclusive('\n').collect::<Vec<_>>();
+
+    let trailing = lines
+      .iter()
+      .rev()
+      .take_while(|line| {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#')
+      })
+      .count();
+
+    let start = match (lines.len() - trailing..lines.len())
+      .find(|&index| lines[index].trim_start().starts_with('#'))
+    {
+      Some(start) => start,
+      None => return,
+    };
+
+    let comments = lines[start..].concat();
+
+    block.text = lines[..start].concat();
+
+    blocks.push(Block {
+      key: None,
+      text: comments,
+    });
+  }
+
+  /// The key a line introduces, if it is a top-level mapping entry.
+  fn key(line: &str) -> Option<String> {
+    if line.starts_with(|c: char| c.is_whitespace() || c == '#' || c == '-') {
+      return None;
+    }
+
+    let (key, rest) = line.split_once(':')?;
+
+    if !(rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+      return None;
+    }
+
+    Some(
+      key
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_owned(),
+    )
   }
 }
 
@@ -50,11 +323,11 @@ mod tests {
   fn cases<'a>() -> Vec<(Matter, &'a str)> {
     vec![
       (
-        Matter {
-          name: "a".into(),
-          tags: Some(vec![String::from("code"), String::from("software")]),
-          links: Some(vec![String::from("b"), String::from("c")]),
-        },
+        Matter::new(
+          "a",
+          Some(vec![String::from("code"), String::from("software")]),
+          Some(vec![String::from("b"), String::from("c")]),
+        ),
         indoc! {"
           ---
           name: a
@@ -68,11 +341,11 @@ mod tests {
         "},
       ),
       (
-        Matter {
-          name: "b".into(),
-          tags: Some(vec![]),
-          links: Some(vec![String::from("b"), String::from("c")]),
-        },
+        Matter::new(
+          "b",
+          Some(vec![]),
+          Some(vec![String::from("b"), String::from("c")]),
+        ),
         indoc! {"
           ---
           name: b
@@ -84,11 +357,11 @@ mod tests {
         "},
       ),
       (
-        Matter {
-          name: "c".into(),
-          tags: Some(vec![String::from("code"), String::from("software")]),
-          links: Some(vec![]),
-        },
+        Matter::new(
+          "c",
+          Some(vec![String::from("code"), String::from("software")]),
+          Some(vec![]),
+        ),
         indoc! {"
           ---
           name: c
@@ -100,11 +373,7 @@ mod tests {
         "},
       ),
       (
-        Matter {
-          name: "d".into(),
-          tags: Some(vec![]),
-          links: Some(vec![]),
-        },
+        Matter::new("d", Some(vec![]), Some(vec![])),
         indoc! {"
           ---
           name: d
@@ -129,4 +398,191 @@ mod tests {
       assert_eq!(Matter::from(have).unwrap(), want);
     }
   }
+
+  #[test]
+  fn timestamps() {
+    let mut matter = Matter::new("a", None, None);
+
+    matter.title = Some("A note".into());
+    matter.created = timestamp::parse("2021-07-01T01:51:09Z");
+    matter.updated = timestamp::parse("2021-07-02");
+
+    let yaml = Matter::into(matter.clone()).unwrap();
+
+    assert_eq!(
+      yaml,
+      indoc! {"
+        ---
+        name: a
+        title: A note
+        tags: ~
+        links: ~
+        created: \"2021-07-01T01:51:09Z\"
+        updated: \"2021-07-02T00:00:00Z\"
+        ---
+      "}
+    );
+
+    assert_eq!(Matter::from(&yaml).unwrap(), matter);
+
+    assert_eq!(
+      Matter::from("name: a\ncreated: 2021-07-01\n")
+        .unwrap()
+        .created
+        .unwrap()
+        .to_rfc3339(),
+      "2021-07-01T00:00:00+00:00"
+    );
+
+    let matter =
+      Matter::from("name: a\ncreated: someday\nupdated: 7\n").unwrap();
+
+    assert_eq!(matter.created, None);
+    assert_eq!(matter.updated, None);
+    assert_eq!(
+      matter.invalid_timestamps(),
+      vec![
+        ("created".to_owned(), "someday".to_owned()),
+        ("updated".to_owned(), "7".to_owned())
+      ]
+    );
+    assert!(Matter::from(&yaml).unwrap().invalid_timestamps().is_empty());
+  }
+
+  #[test]
+  fn split() {
+    let cases = vec![
+      ("---\nname: a\n---\nBody\n", ("name: a\n", "Body\n")),
+      (
+        "---\nname: a\n---\n\n# Title\n",
+        ("name: a\n", "\n# Title\n"),
+      ),
+      ("---\r\nname: a\r\n---\r\nBody", ("name: a\r\n", "Body")),
+      ("---\nname: a\n---\n", ("name: a\n", "")),
+      ("Body\n", ("", "Body\n")),
+      ("---\nname: a\n", ("", "---\nname: a\n")),
+    ];
+
+    for (text, want) in cases {
+      assert_eq!(Matter::split(text), want, "{:?}", text);
+    }
+  }
+
+  #[test]
+  fn round_trip_unknown_fields() {
+    let source = indoc! {"
+      ---
+      # Written by another tool
+      source: https://example.com
+      name: a
+      aliases:
+      - first
+      - second   # the old name
+      tags:
+       - software
+      status: draft
+      ---
+    "};
+
+    assert_eq!(Matter::into(Matter::from(source).unwrap()).unwrap(), source);
+  }
+
+  #[test]
+  fn update_preserves_unknown_fields() {
+    let mut matter = Matter::from(indoc! {"
+      ---
+      # Written by another tool
+      source: https://example.com
+      name: a
+      tags:
+       - software
+      status: draft # or final
+      ---
+    "})
+    .unwrap();
+
+    matter.tags.get_or_insert(Vec::new()).push("code".into());
+    matter.links = Some(vec!["1625104326-b.md".into()]);
+
+    assert_eq!(
+      Matter::into(matter).unwrap(),
+      indoc! {"
+        ---
+        # Written by another tool
+        source: https://example.com
+        name: a
+        tags:
+          - software
+          - code
+        status: draft # or final
+        links:
+          - 1625104326-b.md
+        ---
+      "}
+    );
+  }
+
+  #[test]
+  fn update_list_with_comment() {
+    let mut matter = Matter::from(indoc! {"
+      ---
+      name: a
+      tags:
+        - software
+        # languages
+        - rust
+      # where it came from
+      source: https://example.com
+      ---
+    "})
+    .unwrap();
+
+    matter.tags.get_or_insert(Vec::new()).push("code".into());
+
+    assert_eq!(
+      Matter::into(matter).unwrap(),
+      indoc! {"
+        ---
+        name: a
+        tags:
+          - software
+          - rust
+          - code
+        # where it came from
+        source: https://example.com
+        ---
+      "}
+    );
+  }
+
+  #[test]
+  fn blocks() {
+    assert_eq!(
+      Block::parse(
+        "# comment\nname: a\ntags:\n- b\n# c\n- d\n\n'quoted': c\nurl: http://x\n"
+      ),
+      vec![
+        Block {
+          key: None,
+          text: "# comment\n".into(),
+        },
+        Block {
+          key: Some("name".into()),
+          text: "name: a\n".into(),
+        },
+        Block {
+          key: Some("tags".into()),
+          text: "tags:\n- b\n# c\n- d\n\n".into(),
+        },
+        Block {
+          key: Some("quoted".into()),
+          text: "'quoted': c\n".into(),
+        },
+        Block {
+          key: Some("url".into()),
+          text: "url: http://x\n".into(),
+        },
+      ]
+    );
+  }
 }
@@ -0,0 +1,38 @@
+use {crate::common::*, clap::builder::PossibleValuesParser};
+
+/// Everything `zk new` can fill a note in with.
+#[derive(Debug, Parser)]
+pub(crate) struct NewNote {
+  pub(crate) name: String,
+  #[clap(value_name = "-", value_parser = PossibleValuesParser::new(["-"]))]
+  /// Read the note body from stdin and don't open an editor
+  pub(crate) stdin: Option<String>,
+  #[clap(long)]
+  /// A human readable title, separate from the name
+  pub(crate) title: Option<String>,
+  #[clap(long = "tag", value_name = "TAG")]
+  /// Tag the note, may be repeated
+  pub(crate) tags: Vec<String>,
+  #[clap(long = "link", value_name = "NOTE")]
+  /// Link the note with another one, may be repeated
+  pub(crate) links: Vec<String>,
+  #[clap(long, value_name = "NOTE", conflicts_with = "branch")]
+  /// Continue the Folgezettel sequence of a note, e.g. `1b` after `1a`
+  pub(crate) after: Option<String>,
+  #[clap(long, value_name = "NOTE")]
+  /// Branch off a note's Folgezettel sequence, e.g. `1a1` below `1a`
+  pub(crate) branch: Option<String>,
+  #[clap(long)]
+  /// Start from a template in `.zk/templates` or the templates directory
+  pub(crate) template: Option<String>,
+  #[clap(long)]
+  /// Print the new note's path instead of opening it in the editor
+  pub(crate) no_edit: bool,
+}
+
+impl NewNote {
+  /// Whether the note should be opened once it's written.
+  pub(crate) fn edit(&self) -> bool {
+    !self.no_edit && self.stdin.is_none()
+  }
+}
@@ -9,8 +9,11 @@ pub(crate) struct Note {
 }
 
 impl SkimItem for Note {
-  fn text(&self) -> Cow<str> {
-    Cow::Owned(self.id.to_string())
+  fn text(&self) -> Cow<'_, str> {
+    match &self.matter.title {
+      Some(title) => Cow::Owned(format!("{} {}", self.id, title)),
+      None => Cow::Owned(self.id.to_string()),
+    }
   }
 
   fn preview(&self, _context: PreviewContext) -> ItemPreview {
@@ -19,29 +22,34 @@ impl SkimItem for Note {
 }
 
 impl Note {
-  pub(crate) fn create(path: PathBuf) -> Result<Self> {
+  /// Create a note whose frontmatter and body start out as `text`, which is
+  /// empty unless the note comes from a template.
+  pub(crate) fn create(path: PathBuf, text: &str) -> Result<Self> {
     let id =
       NoteId::parse(path.unwrapped_filename()).ok_or(Error::InvalidNoteId {
         id: path.unwrapped_filename().to_string(),
       })?;
 
-    let mut file = File::create(&path)?;
-    file.write_all(&Matter::default(&id.name)?)?;
+    let (matter, content) = Matter::split(text);
+
+    store::write(
+      &path,
+      &format!("{}{}", Matter::default(&id.name, matter)?, content),
+    )?;
 
     Note::from(path)
   }
 
-  pub(crate) fn from(path: PathBuf) -> Result<Self> {
+  pub(crate) fn new(
+    path: PathBuf,
+    matter: Matter,
+    content: String,
+  ) -> Result<Self> {
     let id =
       NoteId::parse(path.unwrapped_filename()).ok_or(Error::InvalidNoteId {
         id: path.unwrapped_filename().to_string(),
       })?;
 
-    let (matter, content) =
-      matter::matter(&fs::read_to_string(&path)?).unwrap_or_default();
-
-    let matter = Matter::from(matter.as_str())?;
-
     Ok(Self {
       id,
       path,
@@ -50,16 +58,54 @@ impl Note {
     })
   }
 
-  pub(crate) fn has_link(&self, name: &str) -> bool {
+  pub(crate) fn from(path: PathBuf) -> Result<Self> {
+    let text = fs::read_to_string(&path)?;
+
+    let (matter, content) = Matter::split(&text);
+
+    Note::new(path, Matter::from(matter)?, content.to_owned())
+  }
+
+  /// Frontmatter links followed by the links found in the note body.
+  pub(crate) fn links(&self) -> Vec<Link> {
+    self
+      .matter
+      .links
+      .iter()
+      .flatten()
+      .map(|link| Link::matter(link))
+      .chain(Link::parse(&self.content))
+      .collect()
+  }
+
+  /// Whether the note links to the note with filename `name`, in a vault
+  /// with the note `names`.
+  pub(crate) fn has_link(&self, name: &str, names: &Names) -> bool {
     self
       .matter
       .links
       .to_owned()
       .unwrap_or_default()
       .contains(&name.to_string())
+      || NoteId::parse(name).is_some_and(|id| {
+        Link::parse(&self.content)
+          .iter()
+          .any(|link| link.points_to(&id, names))
+      })
   }
 
+  /// Whether the note has the tag `name` or one of its descendants, so a
+  /// note tagged `lang/rust/async` has the tags `lang/rust` and `lang`.
   pub(crate) fn has_tag(&self, name: &str) -> bool {
+    self
+      .matter
+      .tags
+      .iter()
+      .flatten()
+      .any(|tag| tag.is_within(name))
+  }
+
+  pub(crate) fn has_exact_tag(&self, name: &str) -> bool {
     self
       .matter
       .tags
@@ -68,8 +114,8 @@ impl Note {
       .contains(&name.to_string())
   }
 
-  pub(crate) fn add_link(&mut self, name: &str) -> Result<Self> {
-    if self.has_link(name) {
+  pub(crate) fn add_link(&mut self, name: &str, names: &Names) -> Result<Self> {
+    if self.has_link(name, names) {
       return Err(Error::LinkExists {
         link: name.to_string(),
       });
@@ -84,8 +130,12 @@ impl Note {
     })
   }
 
-  pub(crate) fn remove_link(&mut self, name: &str) -> Result<Self> {
-    if !self.has_link(name) {
+  pub(crate) fn remove_link(
+    &mut self,
+    name: &str,
+    names: &Names,
+  ) -> Result<Self> {
+    if !self.has_link(name, names) {
       return Err(Error::LinkMissing {
         link: name.to_string(),
         name: self.id.to_string(),
@@ -97,12 +147,16 @@ impl Note {
         .matter
         .links
         .get_or_insert(Vec::new())
-        .retain(|link| link != name)
+        .retain(|link| link != name);
+
+      if let Some(id) = NoteId::parse(name) {
+        note.content = link::unlink(&note.content, &id, names);
+      }
     })
   }
 
   pub(crate) fn add_tag(&mut self, name: &str) -> Result<Self> {
-    if self.has_tag(name) {
+    if self.has_exact_tag(name) {
       return Err(Error::TagExists {
         tag: name.to_string(),
       });
@@ -117,8 +171,22 @@ impl Note {
     })
   }
 
-  pub(crate) fn remove_tag(&mut self, name: &str) -> Result<Self> {
-    if !self.has_tag(name) {
+  /// Remove the tag `name`, and with `descendants` the tags nested under it
+  /// as well.
+  pub(crate) fn remove_tag(
+    &mut self,
+    name: &str,
+    descendants: bool,
+  ) -> Result<Self> {
+    let removed = |tag: &str| {
+      if descendants {
+        tag.is_within(name)
+      } else {
+        tag == name
+      }
+    };
+
+    if !self.matter.tags.iter().flatten().any(|tag| removed(tag)) {
       return Err(Error::TagMissing {
         tag: name.to_string(),
         name: self.id.to_string(),
@@ -130,19 +198,121 @@ impl Note {
         .matter
         .tags
         .get_or_insert(Vec::new())
-        .retain(|tag| tag != name)
+        .retain(|tag| !removed(tag))
+    })
+  }
+
+  /// Point every link to `old`, in the frontmatter or the body, at `new`.
+  pub(crate) fn relink(
+    &mut self,
+    old: &NoteId,
+    new: &NoteId,
+    names: &Names,
+  ) -> Result<Self> {
+    let (old_filename, new_filename) = (old.to_string(), new.to_string());
+
+    self.write(|note| {
+      for link in note.matter.links.iter_mut().flatten() {
+        if *link == old_filename {
+          *link = new_filename.clone();
+        }
+      }
+
+      note.content = link::relink(&note.content, old, new, names);
     })
   }
 
+  /// Write a copy of this note under a new name, keeping its id prefix. The
+  /// original file is left in place for the caller to remove once nothing
+  /// links to it anymore.
+  pub(crate) fn renamed(&self, name: &str) -> Result<Self> {
+    let id = NoteId {
+      prefix: self.id.prefix.clone(),
+      name: name.to_owned(),
+    };
+
+    let path = self.path.with_file_name(id.to_string());
+
+    if path.exists() {
+      return Err(Error::NoteExists { path });
+    }
+
+    let mut note = self.clone();
+
+    note.id = id;
+    note.path = path;
+
+    note.write(|note| note.matter.name = name.to_owned())
+  }
+
+  /// The note's tags after replacing every tag within one of `sources` with
+  /// the corresponding tag under `target`, or `None` if nothing would change.
+  pub(crate) fn retagged(
+    &self,
+    sources: &[&str],
+    target: &str,
+  ) -> Option<Vec<String>> {
+    let tags = self.matter.tags.as_ref()?;
+
+    let mut retagged = Vec::with_capacity(tags.len());
+
+    for tag in tags {
+      let tag = sources
+        .iter()
+        .find_map(|source| tag.reparent(source, target))
+        .unwrap_or_else(|| tag.to_owned());
+
+      if !retagged.contains(&tag) {
+        retagged.push(tag);
+      }
+    }
+
+    if &retagged == tags {
+      None
+    } else {
+      Some(retagged)
+    }
+  }
+
+  /// Make the frontmatter name match the name in the filename.
+  pub(crate) fn set_name(&mut self) -> Result<Self> {
+    let name = self.id.name.clone();
+    self.write(|note| note.matter.name = name.clone())
+  }
+
+  pub(crate) fn set_tags(&mut self, tags: Vec<String>) -> Result<Self> {
+    self.write(|note| note.matter.tags = Some(tags.clone()))
+  }
+
   pub(crate) fn remove(&self) -> Result<()> {
-    Ok(fs::remove_file(&self.path)?)
+    store::remove(&self.path)
+  }
+
+  /// When the note was created, falling back to the time recorded in its id
+  /// for notes without a `created` field.
+  pub(crate) fn created(&self) -> Option<DateTime<Utc>> {
+    self.matter.created.or_else(|| self.id.created())
+  }
+
+  pub(crate) fn set_content(&mut self, content: &str) -> Result<Self> {
+    self.write(|note| note.content = content.to_owned())
+  }
+
+  pub(crate) fn set_title(&mut self, title: &str) -> Result<Self> {
+    self.write(|note| note.matter.title = Some(title.to_owned()))
+  }
+
+  pub(crate) fn set_sequence(&mut self, sequence: &Sequence) -> Result<Self> {
+    self.write(|note| note.matter.sequence = Some(sequence.clone()))
   }
 
   fn write<F: Fn(&mut Note)>(&mut self, f: F) -> Result<Self> {
     f(self);
-    let mut file = File::create(&self.path)?;
-    file.write_all(Matter::into(self.matter.clone())?.as_bytes())?;
-    file.write_all(self.content.as_bytes())?;
+    self.matter.updated = Some(timestamp::now());
+    store::write(
+      &self.path,
+      &format!("{}{}", Matter::into(self.matter.clone())?, self.content),
+    )?;
     Ok(self.to_owned())
   }
 }
@@ -156,11 +326,77 @@ mod tests {
     in_temp_dir!({
       let mut a = create_note("a").unwrap();
 
-      let link = NoteId::new("b").to_string();
+      let link = NoteId::new("1625104326", "b").to_string();
 
-      a.add_link(&link).unwrap();
+      a.add_link(&link, &Names::default()).unwrap();
 
-      assert!(a.has_link(&link));
+      assert!(a.has_link(&link, &Names::default()));
+    });
+  }
+
+  #[test]
+  fn links() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.add_link("1625104326-b.md", &Names::default()).unwrap();
+      a.content = "See [[c]] and [d](1625104359-d.md).\n".into();
+
+      assert_eq!(
+        a.links()
+          .into_iter()
+          .map(|link| (link.kind, link.target))
+          .collect::<Vec<_>>(),
+        vec![
+          (LinkKind::Matter, "1625104326-b.md".into()),
+          (LinkKind::Wiki, "c".into()),
+          (LinkKind::Markdown, "1625104359-d.md".into()),
+        ]
+      );
+    });
+  }
+
+  #[test]
+  fn has_link_inline() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.content = "See [[b]].\n".into();
+
+      let b = NoteId::parse("1625104326-b.md").unwrap();
+      let names = Names::new([&a.id, &b]);
+
+      assert!(a.has_link("1625104326-b.md", &names));
+      assert!(!a.has_link("1625104344-c.md", &names));
+      assert!(a.add_link("1625104326-b.md", &names).is_err());
+
+      let other = NoteId::parse("1625104344-b.md").unwrap();
+      let names = Names::new([&a.id, &b, &other]);
+
+      assert!(!a.has_link("1625104326-b.md", &names));
+    });
+  }
+
+  #[test]
+  fn remove_link_inline() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.content = "See [[b|the b note]] and [[c]].".into();
+
+      let names = Names::new([
+        &a.id,
+        &NoteId::parse("1625104326-b.md").unwrap(),
+        &NoteId::parse("1625104344-c.md").unwrap(),
+      ]);
+
+      a.remove_link("1625104326-b.md", &names).unwrap();
+
+      assert!(!a.has_link("1625104326-b.md", &names));
+      assert_eq!(
+        Note::from(a.path.clone()).unwrap().content,
+        "See the b note and [[c]]."
+      );
     });
   }
 
@@ -178,13 +414,13 @@ mod tests {
   fn remove_link() {
     in_temp_dir!({
       let mut a = create_note("a").unwrap();
-      let link = NoteId::new("b").to_string();
+      let link = NoteId::new("1625104326", "b").to_string();
 
-      a.add_link(&link).unwrap();
-      assert!(a.has_link(&link));
+      a.add_link(&link, &Names::default()).unwrap();
+      assert!(a.has_link(&link, &Names::default()));
 
-      a.remove_link(&link).unwrap();
-      assert!(!a.has_link(&link));
+      a.remove_link(&link, &Names::default()).unwrap();
+      assert!(!a.has_link(&link, &Names::default()));
     });
   }
 
@@ -196,11 +432,169 @@ mod tests {
       a.add_tag("software").unwrap();
       assert!(a.has_tag("software"));
 
-      a.remove_tag("software").unwrap();
+      a.remove_tag("software", false).unwrap();
       assert!(!a.has_tag("software"));
     });
   }
 
+  #[test]
+  fn hierarchical_tags() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.add_tag("lang/rust/async").unwrap();
+
+      assert!(a.has_tag("lang/rust/async"));
+      assert!(a.has_tag("lang/rust"));
+      assert!(a.has_tag("lang"));
+      assert!(!a.has_tag("lang/go"));
+      assert!(!a.has_exact_tag("lang/rust"));
+
+      a.add_tag("lang/rust").unwrap();
+      a.add_tag("software").unwrap();
+
+      a.remove_tag("lang/rust", false).unwrap();
+
+      assert!(!a.has_exact_tag("lang/rust"));
+      assert!(a.has_exact_tag("lang/rust/async"));
+      assert!(a.remove_tag("lang", false).is_err());
+
+      a.remove_tag("lang", true).unwrap();
+
+      assert!(!a.has_tag("lang"));
+      assert!(a.has_tag("software"));
+    });
+  }
+
+  #[test]
+  fn retagged() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.add_tag("rust").unwrap();
+      a.add_tag("rustlang/async").unwrap();
+      a.add_tag("software").unwrap();
+      a.add_tag("lang/rust").unwrap();
+
+      assert_eq!(
+        a.retagged(&["rust", "rustlang"], "lang/rust").unwrap(),
+        vec!["lang/rust", "lang/rust/async", "software"]
+      );
+
+      assert_eq!(a.retagged(&["go"], "lang/go"), None);
+    });
+  }
+
+  #[test]
+  fn renamed() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      a.content = "Body".into();
+      a.add_tag("software").unwrap();
+
+      let b = a.renamed("b").unwrap();
+
+      assert!(a.path.exists());
+      assert_eq!(b.id.prefix, a.id.prefix);
+      assert_eq!(b.id.name, "b");
+
+      let b = Note::from(b.path).unwrap();
+
+      assert_eq!(b.matter.name, "b");
+      assert!(b.has_tag("software"));
+      assert_eq!(b.content, "Body");
+
+      assert!(a.renamed("b").is_err());
+    });
+  }
+
+  #[test]
+  fn relink() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      let old = NoteId::parse("1625104326-b.md").unwrap();
+      let new = NoteId::parse("1625104326-c.md").unwrap();
+
+      a.add_link(&old.to_string(), &Names::default()).unwrap();
+      a.content = "See [[b]].".into();
+
+      a.relink(&old, &new, &Names::new([&a.id, &old])).unwrap();
+
+      let a = Note::from(a.path).unwrap();
+
+      assert_eq!(a.matter.links.unwrap(), vec![new.to_string()]);
+      assert_eq!(a.content, "See [[c]].");
+    });
+  }
+
+  #[test]
+  fn create_from_template() {
+    in_temp_dir!({
+      let note = Note::create(
+        env::current_dir().unwrap().join("1625104269-sync.md"),
+        indoc! {"
+          ---
+          # meeting notes
+          tags: [meeting]
+          ---
+          # Attendees
+        "},
+      )
+      .unwrap();
+
+      assert_eq!(note.matter.name, "sync");
+      assert_eq!(note.matter.tags, Some(vec!["meeting".into()]));
+      assert!(note.matter.created.is_some());
+      assert_eq!(note.content, "# Attendees\n");
+
+      let text = fs::read_to_string(&note.path).unwrap();
+      assert!(text.contains("# meeting notes\n"));
+    });
+  }
+
+  #[test]
+  fn create_from_template_with_name() {
+    in_temp_dir!({
+      for (i, matter) in ["name : x\n", "'name': x\n", "\"name\": x\n"]
+        .iter()
+        .enumerate()
+      {
+        let note = Note::create(
+          env::current_dir()
+            .unwrap()
+            .join(format!("162510426{}-sync.md", i)),
+          &format!("---\n{}---\n", matter),
+        )
+        .unwrap();
+
+        assert_eq!(note.matter.name, "sync");
+
+        let text = fs::read_to_string(&note.path).unwrap();
+        assert_eq!(text.matches("name").count(), 1, "{}", text);
+      }
+    });
+  }
+
+  #[test]
+  fn timestamps() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+
+      assert!(a.matter.created.is_some());
+      assert_eq!(a.matter.updated, None);
+
+      a.set_title("A note").unwrap();
+
+      let a = Note::from(a.path).unwrap();
+
+      assert_eq!(a.matter.title.as_deref(), Some("A note"));
+      assert!(a.matter.updated >= a.matter.created);
+      assert_eq!(a.text(), format!("{} A note", a.id));
+    });
+  }
+
   #[test]
   fn add_tag_existing() {
     in_temp_dir!({
@@ -218,12 +612,12 @@ mod tests {
     in_temp_dir!({
       let mut a = create_note("a").unwrap();
 
-      let link = NoteId::new("b").to_string();
+      let link = NoteId::new("1625104326", "b").to_string();
 
-      a.add_link(&link).unwrap();
+      a.add_link(&link, &Names::default()).unwrap();
 
-      assert!(a.has_link(&link));
-      assert!(a.add_link(&link).is_err());
+      assert!(a.has_link(&link, &Names::default()));
+      assert!(a.add_link(&link, &Names::default()).is_err());
     });
   }
 
@@ -231,7 +625,7 @@ mod tests {
   fn remove_tag_missing() {
     in_temp_dir!({
       let mut a = create_note("a").unwrap();
-      assert!(a.remove_tag("software").is_err());
+      assert!(a.remove_tag("software", false).is_err());
     });
   }
 
@@ -239,7 +633,7 @@ mod tests {
   fn remove_link_missing() {
     in_temp_dir!({
       let mut a = create_note("a").unwrap();
-      assert!(a.remove_link("b").is_err());
+      assert!(a.remove_link("b", &Names::default()).is_err());
     });
   }
 }
@@ -8,22 +8,41 @@ pub(crate) struct NoteId {
 
 impl Display for NoteId {
   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-    write!(f, "{}-{}.md", self.prefix, self.name)
+    write!(f, "{}.md", self.stem())
   }
 }
 
 impl NoteId {
-  pub(crate) fn new(name: &str) -> Self {
+  pub(crate) fn new(prefix: &str, name: &str) -> Self {
     Self {
-      prefix: chrono::Utc::now()
-        .naive_utc()
-        .and_utc()
-        .timestamp()
-        .to_string(),
+      prefix: prefix.to_owned(),
       name: name.to_owned(),
     }
   }
 
+  /// When the note was created, if the prefix records it.
+  pub(crate) fn created(&self) -> Option<DateTime<Utc>> {
+    self.scheme()?.created(&self.prefix)
+  }
+
+  /// The scheme the prefix was generated with, if it matches any.
+  pub(crate) fn scheme(&self) -> Option<IdScheme> {
+    IdScheme::detect(&self.prefix)
+  }
+
+  /// The note's filename without its extension.
+  pub(crate) fn stem(&self) -> String {
+    format!("{}-{}", self.prefix, self.name)
+  }
+
+  /// Whether `stem` is this id's filename without its extension.
+  pub(crate) fn is_stem(&self, stem: &str) -> bool {
+    stem
+      .strip_prefix(self.prefix.as_str())
+      .and_then(|rest| rest.strip_prefix('-'))
+      == Some(self.name.as_str())
+  }
+
   pub(crate) fn parse(filename: &str) -> Option<Self> {
     let mut split = filename[..filename.rfind('.').unwrap_or(filename.len())]
       .splitn(2, |c| ['-', ' '].contains(&c));
@@ -61,4 +80,55 @@ mod tests {
       assert_eq!(id.name, name);
     }
   }
+
+  #[test]
+  fn created() {
+    assert_eq!(
+      NoteId::parse("1625104269-a.md")
+        .unwrap()
+        .created()
+        .unwrap()
+        .to_rfc3339(),
+      "2021-07-01T01:51:09+00:00"
+    );
+
+    assert_eq!(NoteId::parse("a-b.md").unwrap().created(), None);
+  }
+
+  #[test]
+  fn schemes() {
+    let cases = vec![
+      ("1625104269-a.md", Some(IdScheme::Unix)),
+      ("1625104269000-a.md", Some(IdScheme::Millis)),
+      ("202107010151-a.md", Some(IdScheme::Datetime)),
+      ("01F9H1N5M8Q7VJ7Y2G6K3V4B5C-a.md", Some(IdScheme::Ulid)),
+      ("k3x9q2ab-a.md", Some(IdScheme::Base36)),
+      ("b-a.md", None),
+    ];
+
+    for (filename, scheme) in cases {
+      let id = NoteId::parse(filename).unwrap();
+      assert_eq!(id.name, "a", "{}", filename);
+      assert_eq!(id.scheme(), scheme, "{}", filename);
+    }
+
+    assert_eq!(
+      NoteId::parse("202107010151-a.md")
+        .unwrap()
+        .created()
+        .unwrap()
+        .to_rfc3339(),
+      "2021-07-01T01:51:00+00:00"
+    );
+  }
+
+  #[test]
+  fn is_stem() {
+    let id = NoteId::parse("1625104269-a-b.md").unwrap();
+
+    assert!(id.is_stem("1625104269-a-b"));
+    assert!(!id.is_stem("1625104269-a"));
+    assert!(!id.is_stem("1625104269a-b"));
+    assert!(!id.is_stem("a-b"));
+  }
 }
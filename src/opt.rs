@@ -15,63 +15,91 @@ use crate::common::*;
 {all-args}{after-help}
 "
 )]
-pub(crate) enum Opt {
-  #[clap(name = "dir", alias = "d")]
-  /// Output the Zettelkasten directory path
-  Dir,
-  #[clap(name = "explore", alias = "e")]
-  /// Explore note links
-  Explore { name: String },
-  #[clap(name = "find", alias = "f")]
-  /// Find notes by tag
-  Find { tag: String },
-  #[clap(name = "link", alias = "l")]
-  /// Link two notes
-  Link { left: String, right: String },
-  #[clap(name = "new", alias = "n")]
-  /// Create a new note
-  New { name: String },
-  #[clap(name = "open", alias = "o")]
-  /// Open a note
-  Open { name: String },
-  #[clap(name = "rm")]
-  /// Remove a note
-  Remove { name: String },
-  #[clap(name = "rmtag", alias = "rt")]
-  /// Remove a tag from a note
-  RemoveTag { name: String, tag: String },
-  #[clap(name = "rmlink", alias = "rl")]
-  /// Remove a link between two notes
-  RemoveLink { left: String, right: String },
-  #[clap(name = "search", alias = "s")]
-  /// Fuzzy search notes
-  Search,
-  #[clap(name = "tag", alias = "t")]
-  /// Add a tag to a note
-  Tag { name: String, tag: String },
+pub(crate) struct Opt {
+  #[clap(long, global = true, value_name = "DIR", conflicts_with = "vault")]
+  /// Use the vault in DIR, overriding the configured path
+  dir: Option<PathBuf>,
+  #[clap(long, global = true, value_name = "NAME")]
+  /// Use the vault configured as NAME under `[vaults]`
+  vault: Option<String>,
+  #[clap(long, global = true, group = "selection")]
+  /// Select every matching note
+  all: bool,
+  #[clap(long, global = true, group = "selection")]
+  /// Select the first matching note
+  first: bool,
+  #[clap(long, global = true, group = "selection", value_name = "PREFIX")]
+  /// Select the note whose id starts with PREFIX
+  id: Option<String>,
+  #[clap(long, global = true, group = "selection")]
+  /// Select the most recently created matching note
+  latest: bool,
+  #[clap(subcommand)]
+  subcommand: Subcommand,
 }
 
 impl Opt {
+  fn selection(&self) -> Selection {
+    if self.all {
+      Selection::All
+    } else if self.first {
+      Selection::First
+    } else if let Some(prefix) = &self.id {
+      Selection::Id(prefix.to_owned())
+    } else if self.latest {
+      Selection::Latest
+    } else {
+      Selection::Interactive
+    }
+  }
+
   pub(crate) fn run(self) -> Result<(), Error> {
-    let config = Config::load()?;
-
-    let handler =
-      Handler::new(config.clone(), Directory::new(config.path.expand()));
-
-    match self {
-      Opt::Dir => handler.dir(),
-      Opt::Explore { name } => handler.explore(&name)?,
-      Opt::Find { tag } => handler.find(&tag)?,
-      Opt::Link { left, right } => handler.link(&left, &right)?,
-      Opt::New { name } => handler.create(&name)?,
-      Opt::Open { name } => handler.open(&name)?,
-      Opt::Remove { name } => handler.remove(&name)?,
-      Opt::RemoveLink { left, right } => handler.remove_link(&left, &right)?,
-      Opt::RemoveTag { name, tag } => handler.remove_tag(&name, &tag)?,
-      Opt::Search => handler.search()?,
-      Opt::Tag { name, tag } => handler.tag(&name, &tag)?,
+    let mut flags = Vec::new();
+
+    if let Some(dir) = &self.dir {
+      flags.push(("--dir", "path", dir.display().to_string()));
+    }
+
+    if let Some(vault) = &self.vault {
+      flags.push(("--vault", "vault", vault.clone()));
+    }
+
+    let config = Config::load(&flags)?;
+
+    let directory = Directory::new(config.vault_path()?).with_vaults(
+      config
+        .vaults
+        .iter()
+        .map(|(name, path)| (name.clone(), path.expand()))
+        .collect(),
+    );
+
+    let root = directory.path.clone();
+    let data_dir = directory.data_dir();
+
+    let handler = Handler::new(config, directory, self.selection());
+
+    if !self.subcommand.is_recorded() {
+      return self.subcommand.run(handler);
+    }
+
+    let mut history = History::load(&data_dir)?;
+
+    let (result, changes) = store::record(|| self.subcommand.run(handler));
+
+    if !changes.is_empty() {
+      history.push(
+        &root,
+        &shell_words::join(
+          env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned()),
+        ),
+        changes,
+      );
+      history.save(&data_dir)?;
     }
 
-    Ok(())
+    result
   }
 }
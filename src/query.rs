@@ -0,0 +1,263 @@
+use crate::common::*;
+
+/// A boolean expression over note tags, such as `(rust or go) and not draft`
+/// or `lang/*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Query {
+  And(Box<Query>, Box<Query>),
+  /// A tag without its descendants, written with a leading `=`
+  Exact(String),
+  Not(Box<Query>),
+  Or(Box<Query>, Box<Query>),
+  /// Tags starting with a prefix, written with a trailing `*`
+  Prefix(String),
+  /// A tag or any of its descendants
+  Tag(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  And,
+  Close,
+  Not,
+  Open,
+  Or,
+  Word(String),
+}
+
+impl Display for Query {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Query::And(left, right) => write!(f, "({} and {})", left, right),
+      Query::Exact(tag) => write!(f, "={}", tag),
+      Query::Not(query) => write!(f, "not {}", query),
+      Query::Or(left, right) => write!(f, "({} or {})", left, right),
+      Query::Prefix(prefix) => write!(f, "{}*", prefix),
+      Query::Tag(tag) => write!(f, "{}", tag),
+    }
+  }
+}
+
+impl Query {
+  pub(crate) fn parse(text: &str) -> Result<Self> {
+    let tokens = Self::lex(text);
+
+    let mut parser = Parser {
+      tokens: &tokens,
+      position: 0,
+    };
+
+    let query = parser.or()?;
+
+    match tokens.get(parser.position) {
+      Some(token) => Err(Error::TagQuery {
+        message: format!("unexpected `{}`", token),
+      }),
+      None => Ok(query),
+    }
+  }
+
+  pub(crate) fn matches(&self, note: &Note) -> bool {
+    match self {
+      Query::And(left, right) => left.matches(note) && right.matches(note),
+      Query::Exact(tag) => note.has_exact_tag(tag),
+      Query::Not(query) => !query.matches(note),
+      Query::Or(left, right) => left.matches(note) || right.matches(note),
+      Query::Prefix(prefix) => note
+        .matter
+        .tags
+        .iter()
+        .flatten()
+        .any(|tag| tag.starts_with(prefix.as_str())),
+      Query::Tag(tag) => note.has_tag(tag),
+    }
+  }
+
+  fn lex(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+
+    let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+      if !word.is_empty() {
+        tokens.push(match word.to_lowercase().as_str() {
+          "and" => Token::And,
+          "not" => Token::Not,
+          "or" => Token::Or,
+          _ => Token::Word(word.clone()),
+        });
+        word.clear();
+      }
+    };
+
+    for c in text.chars() {
+      match c {
+        '(' | ')' => {
+          flush(&mut word, &mut tokens);
+          tokens.push(if c == '(' { Token::Open } else { Token::Close });
+        }
+        c if c.is_whitespace() => flush(&mut word, &mut tokens),
+        c => word.push(c),
+      }
+    }
+
+    flush(&mut word, &mut tokens);
+
+    tokens
+  }
+}
+
+impl Display for Token {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Token::And => write!(f, "and"),
+      Token::Close => write!(f, ")"),
+      Token::Not => write!(f, "not"),
+      Token::Open => write!(f, "("),
+      Token::Or => write!(f, "or"),
+      Token::Word(word) => write!(f, "{}", word),
+    }
+  }
+}
+
+struct Parser<'a> {
+  tokens: &'a [Token],
+  position: usize,
+}
+
+impl Parser<'_> {
+  fn accept(&mut self, token: &Token) -> bool {
+    if self.tokens.get(self.position) == Some(token) {
+      self.position += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn or(&mut self) -> Result<Query> {
+    let mut query = self.and()?;
+
+    while self.accept(&Token::Or) {
+      query = Query::Or(Box::new(query), Box::new(self.and()?));
+    }
+
+    Ok(query)
+  }
+
+  fn and(&mut self) -> Result<Query> {
+    let mut query = self.not()?;
+
+    while self.accept(&Token::And) {
+      query = Query::And(Box::new(query), Box::new(self.not()?));
+    }
+
+    Ok(query)
+  }
+
+  fn not(&mut self) -> Result<Query> {
+    if self.accept(&Token::Not) {
+      Ok(Query::Not(Box::new(self.not()?)))
+    } else {
+      self.atom()
+    }
+  }
+
+  fn atom(&mut self) -> Result<Query> {
+    let token = self.tokens.get(self.position).cloned();
+
+    self.position += 1;
+
+    match token {
+      Some(Token::Open) => {
+        let query = self.or()?;
+
+        if !self.accept(&Token::Close) {
+          return Err(Error::TagQuery {
+            message: "missing `)`".into(),
+          });
+        }
+
+        Ok(query)
+      }
+      Some(Token::Word(word)) => {
+        Ok(match (word.strip_prefix('='), word.strip_suffix('*')) {
+          (Some(tag), _) => Query::Exact(tag.to_owned()),
+          (None, Some(prefix)) => Query::Prefix(prefix.to_owned()),
+          (None, None) => Query::Tag(word),
+        })
+      }
+      Some(token) => Err(Error::TagQuery {
+        message: format!("expected a tag, found `{}`", token),
+      }),
+      None => Err(Error::TagQuery {
+        message: "expected a tag, found end of query".into(),
+      }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    let cases = vec![
+      ("software", "software"),
+      ("software and not archived", "(software and not archived)"),
+      ("(rust or go) and draft", "((rust or go) and draft)"),
+      ("rust or go and draft", "(rust or (go and draft))"),
+      ("a and b and c", "((a and b) and c)"),
+      ("not not a", "not not a"),
+      ("lang/*", "lang/*"),
+      ("=lang/rust", "=lang/rust"),
+      ("NOT a AND b", "(not a and b)"),
+      ("(a)", "a"),
+    ];
+
+    for (text, want) in cases {
+      assert_eq!(Query::parse(text).unwrap().to_string(), want, "{}", text);
+    }
+  }
+
+  #[test]
+  fn parse_errors() {
+    for text in ["", "a and", "(a or b", "a b", "and a", "a )", "not"] {
+      assert!(Query::parse(text).is_err(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn matches() {
+    let cases = vec![
+      ("software and not archived", &["software"][..], true),
+      (
+        "software and not archived",
+        &["software", "archived"],
+        false,
+      ),
+      ("(rust or go) and draft", &["go", "draft"], true),
+      ("(rust or go) and draft", &["rust"], false),
+      ("lang/*", &["lang/rust"], true),
+      ("lang/*", &["language"], false),
+      ("not lang/*", &[], true),
+      ("lang/rust", &["lang/rust/async"], true),
+      ("lang", &["lang/rust/async"], true),
+      ("lang/rust", &["lang/rustacean"], false),
+      ("=lang/rust", &["lang/rust/async"], false),
+      ("=lang/rust", &["lang/rust"], true),
+    ];
+
+    for (text, tags, want) in cases {
+      assert_eq!(
+        Query::parse(text)
+          .unwrap()
+          .matches(&note("1625104269-a.md", tags, "")),
+        want,
+        "{} {:?}",
+        text,
+        tags
+      );
+    }
+  }
+}
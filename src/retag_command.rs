@@ -0,0 +1,45 @@
+use crate::common::*;
+
+#[derive(Debug, Parser)]
+pub(crate) enum RetagCommand {
+  #[clap(name = "merge")]
+  /// Replace several tags with a single one across the vault
+  Merge {
+    #[clap(required = true)]
+    tags: Vec<String>,
+    #[clap(long)]
+    /// The tag replacing the merged tags
+    into: String,
+    #[clap(long)]
+    /// Report the notes that would change without writing them
+    dry_run: bool,
+  },
+  #[clap(name = "rename")]
+  /// Rename a tag across the vault
+  Rename {
+    old: String,
+    new: String,
+    #[clap(long)]
+    /// Report the notes that would change without writing them
+    dry_run: bool,
+  },
+}
+
+impl RetagCommand {
+  pub(crate) fn run(self, handler: &Handler) -> Result<()> {
+    match self {
+      Self::Merge {
+        tags,
+        into,
+        dry_run,
+      } => handler.retag(
+        &tags.iter().map(String::as_str).collect::<Vec<_>>(),
+        &into,
+        dry_run,
+      ),
+      Self::Rename { old, new, dry_run } => {
+        handler.retag(&[&old], &new, dry_run)
+      }
+    }
+  }
+}
@@ -0,0 +1,165 @@
+use crate::common::*;
+
+/// How to pick between several matching notes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) enum Selection {
+  /// Every matching note
+  All,
+  /// The first matching note, ordered by filename
+  First,
+  /// The note whose id prefix starts with the given string
+  Id(String),
+  /// Ask through the fuzzy finder
+  #[default]
+  Interactive,
+  /// The most recently created matching note
+  Latest,
+}
+
+impl Selection {
+  pub(crate) fn select(&self, mut notes: Vec<Note>) -> Result<Vec<Note>> {
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    self.select_in_order(notes)
+  }
+
+  /// Pick from `notes` in the order they are given in, such as by relevance,
+  /// rather than by filename.
+  pub(crate) fn select_in_order(&self, notes: Vec<Note>) -> Result<Vec<Note>> {
+    match self {
+      Selection::All => Ok(notes),
+      Selection::First => Ok(notes.into_iter().take(1).collect()),
+      Selection::Id(prefix) => {
+        let notes = notes
+          .into_iter()
+          .filter(|note| note.id.prefix.starts_with(prefix.as_str()))
+          .collect::<Vec<_>>();
+
+        match notes.len() {
+          0 => Err(Error::IdNotFound {
+            prefix: prefix.to_owned(),
+          }),
+          1 => Ok(notes),
+          count => Err(Error::IdAmbiguous {
+            prefix: prefix.to_owned(),
+            count,
+          }),
+        }
+      }
+      Selection::Interactive => {
+        if notes.len() <= 1 {
+          return Ok(notes);
+        }
+
+        if !Self::is_terminal() {
+          return Err(Error::TerminalRequired);
+        }
+
+        Search::new(notes).run()
+      }
+      Selection::Latest => Ok(
+        notes
+          .into_iter()
+          .max_by(|a, b| {
+            (a.created(), &a.id.prefix).cmp(&(b.created(), &b.id.prefix))
+          })
+          .into_iter()
+          .collect(),
+      ),
+    }
+  }
+
+  pub(crate) fn is_terminal() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn notes() -> Vec<Note> {
+    ["1625104326-b.md", "1625104269-a.md", "1625104344-a.md"]
+      .iter()
+      .map(|filename| note(filename, &[], ""))
+      .collect()
+  }
+
+  fn names(notes: Vec<Note>) -> Vec<String> {
+    notes.into_iter().map(|note| note.id.to_string()).collect()
+  }
+
+  #[test]
+  fn all() {
+    assert_eq!(
+      names(Selection::All.select(notes()).unwrap()),
+      vec!["1625104269-a.md", "1625104326-b.md", "1625104344-a.md"]
+    );
+  }
+
+  #[test]
+  fn all_in_order() {
+    assert_eq!(
+      names(Selection::All.select_in_order(notes()).unwrap()),
+      vec!["1625104326-b.md", "1625104269-a.md", "1625104344-a.md"]
+    );
+  }
+
+  #[test]
+  fn first() {
+    assert_eq!(
+      names(Selection::First.select(notes()).unwrap()),
+      vec!["1625104269-a.md"]
+    );
+  }
+
+  #[test]
+  fn latest() {
+    assert_eq!(
+      names(Selection::Latest.select(notes()).unwrap()),
+      vec!["1625104344-a.md"]
+    );
+  }
+
+  #[test]
+  fn latest_mixed_schemes() {
+    let notes = ["1625104300000-a.md", "1625104344-a.md", "k3x9q2ab-a.md"]
+      .iter()
+      .map(|filename| note(filename, &[], ""))
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      names(Selection::Latest.select(notes).unwrap()),
+      vec!["1625104344-a.md"]
+    );
+  }
+
+  #[test]
+  fn id() {
+    assert_eq!(
+      names(Selection::Id("162510432".into()).select(notes()).unwrap()),
+      vec!["1625104326-b.md"]
+    );
+
+    assert!(matches!(
+      Selection::Id("1625104".into()).select(notes()),
+      Err(Error::IdAmbiguous { count: 3, .. })
+    ));
+
+    assert!(matches!(
+      Selection::Id("2".into()).select(notes()),
+      Err(Error::IdNotFound { .. })
+    ));
+  }
+
+  #[test]
+  fn interactive_single() {
+    assert_eq!(
+      Selection::Interactive
+        .select(notes().into_iter().take(1).collect())
+        .unwrap()
+        .len(),
+      1
+    );
+  }
+}
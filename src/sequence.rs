@@ -0,0 +1,352 @@
+use crate::common::*;
+
+/// A Luhmann-style Folgezettel position such as `1`, `1a`, `1a1` or `1b`.
+///
+/// Segments alternate between numbers and letters, each one branching off the
+/// note before it. Letters are stored as bijective base 26 numbers, so `a` is
+/// 1, `z` is 26 and `aa` is 27, which makes the derived ordering the reading
+/// order of the sequence: `1`, `1a`, `1a1`, `1b`, `2`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Sequence(Vec<u64>);
+
+impl Sequence {
+  pub(crate) fn root(number: u64) -> Self {
+    Self(vec![number])
+  }
+
+  pub(crate) fn parse(text: &str) -> Result<Self> {
+    let invalid = || Error::SequenceInvalid {
+      text: text.to_owned(),
+    };
+
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+      let letters = segments.len() % 2 == 1;
+
+      let end = rest
+        .find(|c: char| {
+          if letters {
+            !c.is_ascii_lowercase()
+          } else {
+            !c.is_ascii_digit()
+          }
+        })
+        .unwrap_or(rest.len());
+
+      let (segment, tail) = rest.split_at(end);
+
+      let value = if letters {
+        segment.bytes().try_fold(0u64, |value, b| {
+          value.checked_mul(26)?.checked_add(u64::from(b - b'a' + 1))
+        })
+      } else {
+        segment.parse().ok()
+      };
+
+      match value {
+        Some(value) if value > 0 => segments.push(value),
+        _ => return Err(invalid()),
+      }
+
+      rest = tail;
+    }
+
+    if segments.is_empty() {
+      return Err(invalid());
+    }
+
+    Ok(Self(segments))
+  }
+
+  /// The sequence this one branches off, if it isn't a root.
+  pub(crate) fn parent(&self) -> Option<Self> {
+    match self.0.len() {
+      1 => None,
+      len => Some(Self(self.0[..len - 1].to_vec())),
+    }
+  }
+
+  /// Whether `other` is somewhere below this sequence.
+  pub(crate) fn is_ancestor_of(&self, other: &Self) -> bool {
+    other.0.len() > self.0.len() && other.0.starts_with(&self.0)
+  }
+
+  /// The sequence that continues this one, e.g. `1b` after `1a`.
+  pub(crate) fn next_sibling(&self) -> Self {
+    let mut segments = self.0.clone();
+    *segments.last_mut().unwrap() += 1;
+    Self(segments)
+  }
+
+  /// The first sequence branching off this one, e.g. `1a1` below `1a`.
+  pub(crate) fn first_child(&self) -> Self {
+    let mut segments = self.0.clone();
+    segments.push(1);
+    Self(segments)
+  }
+
+  /// Where a note placed after this one goes among the `taken` sequences:
+  /// the next sibling if it is free, and otherwise, since the train already
+  /// continues there, a new branch below this one.
+  pub(crate) fn after(&self, taken: &BTreeSet<Sequence>) -> Self {
+    let sibling = self.next_sibling();
+
+    if taken.contains(&sibling) {
+      self.below(taken)
+    } else {
+      sibling
+    }
+  }
+
+  /// The first sequence branching off this one that isn't `taken`.
+  pub(crate) fn below(&self, taken: &BTreeSet<Sequence>) -> Self {
+    let mut child = self.first_child();
+
+    while taken.contains(&child) {
+      child = child.next_sibling();
+    }
+
+    child
+  }
+
+  fn letters(mut value: u64) -> String {
+    let mut letters = Vec::new();
+
+    while value > 0 {
+      value -= 1;
+      letters.push(b'a' + (value % 26) as u8);
+      value /= 26;
+    }
+
+    letters.reverse();
+
+    String::from_utf8(letters).unwrap()
+  }
+}
+
+impl Display for Sequence {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    for (i, segment) in self.0.iter().enumerate() {
+      if i % 2 == 0 {
+        write!(f, "{}", segment)?;
+      } else {
+        write!(f, "{}", Self::letters(*segment))?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Serialize for Sequence {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl Sequence {
+  /// Read an optional frontmatter sequence. A root sequence like `1` is
+  /// written as a bare YAML number, and any value that isn't a sequence, such
+  /// as another tool's use of the key, is read as no sequence rather than
+  /// failing the whole note. It stays in the frontmatter as it was, for
+  /// `zk doctor` to report.
+  pub(crate) fn deserialize_optional<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Option<Self>, D::Error> {
+    Ok(
+      match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+        Some(serde_yaml::Value::Number(number)) => {
+          Sequence::parse(&number.to_string()).ok()
+        }
+        Some(serde_yaml::Value::String(text)) => Sequence::parse(&text).ok(),
+        _ => None,
+      },
+    )
+  }
+}
+
+/// Notes arranged by their Folgezettel sequence.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceTree {
+  children: BTreeMap<Sequence, (Vec<String>, SequenceTree)>,
+}
+
+impl SequenceTree {
+  /// Notes whose sequence's parent has no note hang off their nearest
+  /// ancestor that does, or become roots themselves.
+  pub(crate) fn new(notes: &[Note]) -> Self {
+    let mut sequenced = notes
+      .iter()
+      .filter_map(|note| Some((note.matter.sequence.clone()?, note)))
+      .collect::<Vec<_>>();
+
+    sequenced.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut tree = Self::default();
+
+    for (sequence, note) in sequenced {
+      tree.insert(sequence, note.id.name.clone());
+    }
+
+    tree
+  }
+
+  fn insert(&mut self, sequence: Sequence, name: String) {
+    match self
+      .children
+      .iter_mut()
+      .find(|(parent, _)| parent.is_ancestor_of(&sequence))
+    {
+      Some((_, (_, child))) => child.insert(sequence, name),
+      None => self.children.entry(sequence).or_default().0.push(name),
+    }
+  }
+}
+
+impl Tree for SequenceTree {
+  fn nodes(&self) -> Vec<(String, &Self)> {
+    self
+      .children
+      .iter()
+      .map(|(sequence, (names, child))| {
+        (format!("{} {}", sequence, names.join(", ")), child)
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sequence(text: &str) -> Sequence {
+    Sequence::parse(text).unwrap()
+  }
+
+  #[test]
+  fn parse() {
+    for text in ["1", "1a", "1a1", "12ab3", "2z", "1aa"] {
+      assert_eq!(sequence(text).to_string(), text);
+    }
+
+    for text in ["", "a", "0", "1A", "1-a", "1a0", "1 a"] {
+      assert!(Sequence::parse(text).is_err(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn ordering() {
+    let mut sequences = vec!["2", "1b", "1a1", "1", "1aa", "1z", "1a", "10"]
+      .into_iter()
+      .map(sequence)
+      .collect::<Vec<_>>();
+
+    sequences.sort();
+
+    assert_eq!(
+      sequences
+        .iter()
+        .map(Sequence::to_string)
+        .collect::<Vec<_>>(),
+      vec!["1", "1a", "1a1", "1b", "1z", "1aa", "2", "10"]
+    );
+  }
+
+  #[test]
+  fn navigation() {
+    assert_eq!(sequence("1").next_sibling(), sequence("2"));
+    assert_eq!(sequence("1a").next_sibling(), sequence("1b"));
+    assert_eq!(sequence("1z").next_sibling(), sequence("1aa"));
+    assert_eq!(sequence("1").first_child(), sequence("1a"));
+    assert_eq!(sequence("1a").first_child(), sequence("1a1"));
+    assert_eq!(sequence("1a1").parent(), Some(sequence("1a")));
+    assert_eq!(sequence("1").parent(), None);
+    assert!(sequence("1").is_ancestor_of(&sequence("1a1")));
+    assert!(!sequence("1a").is_ancestor_of(&sequence("1a")));
+    assert!(!sequence("1a").is_ancestor_of(&sequence("1b")));
+  }
+
+  #[test]
+  fn placement() {
+    let taken = ["1", "1a", "1b", "1b1", "2"]
+      .iter()
+      .map(|text| sequence(text))
+      .collect::<BTreeSet<_>>();
+
+    assert_eq!(sequence("1b").after(&taken), sequence("1c"));
+    assert_eq!(sequence("1a").after(&taken), sequence("1a1"));
+    assert_eq!(sequence("1").after(&taken), sequence("1c"));
+    assert_eq!(sequence("2").after(&taken), sequence("3"));
+    assert_eq!(sequence("1").below(&taken), sequence("1c"));
+    assert_eq!(sequence("1b").below(&taken), sequence("1b2"));
+    assert_eq!(sequence("2").below(&taken), sequence("2a"));
+  }
+
+  #[test]
+  fn serde() {
+    let matter = Matter::from("name: a\nsequence: 1\n").unwrap();
+    assert_eq!(matter.sequence, Some(sequence("1")));
+
+    let matter = Matter::from("name: a\nsequence: 1a2\n").unwrap();
+    assert_eq!(matter.sequence, Some(sequence("1a2")));
+
+    let matter = Matter::from("name: a\nsequence: intro\n").unwrap();
+    assert_eq!(matter.sequence, None);
+    assert_eq!(matter.invalid_sequence().as_deref(), Some("intro"));
+
+    assert_eq!(
+      Matter::into(Matter {
+        title: Some("A".into()),
+        ..matter
+      })
+      .unwrap(),
+      "---\nname: a\nsequence: intro\ntitle: A\n---\n"
+    );
+
+    assert_eq!(Matter::from("name: a\n").unwrap().invalid_sequence(), None);
+  }
+
+  #[test]
+  fn tree() {
+    let note = |name: &str, sequence: Option<&str>| {
+      let mut note = Note::new(
+        PathBuf::from(format!("1625104269-{}.md", name)),
+        Matter::new(name, None, None),
+        String::new(),
+      )
+      .unwrap();
+      note.matter.sequence = sequence.map(super::tests::sequence);
+      note
+    };
+
+    let tree = SequenceTree::new(&[
+      note("c", Some("1a1")),
+      note("a", Some("1")),
+      note("e", Some("2")),
+      note("d", Some("1b")),
+      note("b", Some("1a")),
+      note("f", Some("2a1")),
+      note("g", None),
+    ]);
+
+    let mut out = Vec::new();
+    tree.write(&mut out).unwrap();
+
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      indoc! {"
+        1 a
+        ├── 1a b
+        │   └── 1a1 c
+        └── 1b d
+        2 e
+        └── 2a1 f
+      "}
+    );
+  }
+}
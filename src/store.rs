@@ -0,0 +1,319 @@
+//! Every change `zk` makes to the files in a vault goes through here, so the
+//! changes a command makes can be recorded for `zk undo` and `zk redo`, and
+//! rolled back when an operation on several notes fails halfway.
+
+use {
+  crate::common::*,
+  std::{
+    cell::RefCell,
+    fs::File,
+    sync::atomic::{AtomicUsize, Ordering},
+  },
+};
+
+thread_local! {
+  /// For every recording and transaction in progress, the contents each
+  /// changed file had before it was first changed, `None` if it didn't exist.
+  static FRAMES: RefCell<Vec<BTreeMap<PathBuf, Option<String>>>> =
+    const { RefCell::new(Vec::new()) };
+}
+
+/// Bumped before every change made through here, so that notes read at an
+/// earlier generation are known to be out of date.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of changes made so far.
+pub(crate) fn generation() -> usize {
+  GENERATION.load(Ordering::SeqCst)
+}
+
+/// Remember what `path` contains before it is first changed. A file that
+/// can't be read back, such as one that isn't UTF-8, is an error rather than
+/// being taken for a missing one, which rolling back would then delete.
+fn touch(path: &Path) -> io::Result<()> {
+  GENERATION.fetch_add(1, Ordering::SeqCst);
+
+  FRAMES.with(|frames| {
+    let mut frames = frames.borrow_mut();
+
+    if frames.iter().all(|frame| frame.contains_key(path)) {
+      return Ok(());
+    }
+
+    let contents = match fs::read_to_string(path) {
+      Ok(contents) => Some(contents),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+      Err(error) => return Err(error),
+    };
+
+    for frame in frames.iter_mut() {
+      frame
+        .entry(path.to_owned())
+        .or_insert_with(|| contents.clone());
+    }
+
+    Ok(())
+  })
+}
+
+/// Run `f`, returning its result along with the contents every file it
+/// changed had before.
+fn frame<T>(
+  f: impl FnOnce() -> Result<T>,
+) -> (Result<T>, BTreeMap<PathBuf, Option<String>>) {
+  FRAMES.with(|frames| frames.borrow_mut().push(BTreeMap::new()));
+
+  let result = f();
+
+  let before = FRAMES
+    .with(|frames| frames.borrow_mut().pop())
+    .unwrap_or_default();
+
+  (result, before)
+}
+
+/// Replace `path` with `contents` by writing a temporary file next to it and
+/// renaming it into place, so that the file is never left half written.
+///
+/// Unlike `write`, the change isn't recorded, which suits files such as the
+/// index that `zk` keeps for itself.
+pub(crate) fn replace(path: &Path, contents: &str) -> io::Result<()> {
+  let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+  fs::create_dir_all(dir)?;
+
+  // A short name of its own rather than one derived from the note's, which
+  // could be too long for the file system.
+  static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+  let temp = dir.join(format!(
+    ".zk-{}-{}.tmp",
+    process::id(),
+    COUNT.fetch_add(1, Ordering::Relaxed)
+  ));
+
+  let result = (|| {
+    let mut file = File::create(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    if let Ok(metadata) = fs::metadata(path) {
+      fs::set_permissions(&temp, metadata.permissions())?;
+    }
+
+    fs::rename(&temp, path)?;
+
+    #[cfg(unix)]
+    if !dir.as_os_str().is_empty() {
+      File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+  })();
+
+  if result.is_err() {
+    fs::remove_file(&temp).ok();
+  }
+
+  result
+}
+
+/// Write `contents` to `path`, creating its directory if needed.
+pub(crate) fn write(path: &Path, contents: &str) -> Result<()> {
+  touch(path)?;
+  Ok(replace(path, contents)?)
+}
+
+pub(crate) fn rename(from: &Path, to: &Path) -> Result<()> {
+  touch(from)?;
+  touch(to)?;
+  Ok(fs::rename(from, to)?)
+}
+
+pub(crate) fn remove(path: &Path) -> Result<()> {
+  touch(path)?;
+  Ok(fs::remove_file(path)?)
+}
+
+/// Remove `dir` along with every file in it.
+pub(crate) fn remove_dir(dir: &Path) -> Result<()> {
+  for entry in WalkDir::new(dir) {
+    let entry = entry?;
+
+    if entry.file_type().is_file() {
+      touch(entry.path())?;
+    }
+  }
+
+  Ok(fs::remove_dir_all(dir)?)
+}
+
+/// Run `f`, returning its result along with every file it changed.
+pub(crate) fn record<T>(
+  f: impl FnOnce() -> Result<T>,
+) -> (Result<T>, Vec<Change>) {
+  let (result, before) = frame(f);
+
+  let changes = before
+    .into_iter()
+    .map(|(path, before)| Change {
+      after: fs::read_to_string(&path).ok(),
+      path,
+      before,
+    })
+    .filter(|change| change.before != change.after)
+    .collect();
+
+  (result, changes)
+}
+
+/// Run `f`, putting every file it changed back the way it was if it fails, so
+/// that either all of its changes are made or none are.
+///
+/// Rolling back is best effort: a file that can't be restored doesn't hide
+/// the error `f` failed with. It also only happens within the running
+/// process, so if `zk` is killed halfway the files already written stay so.
+pub(crate) fn transaction<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+  let (result, before) = frame(f);
+
+  if result.is_err() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+
+    for (path, contents) in before {
+      match contents {
+        Some(contents) => replace(&path, &contents).ok(),
+        None => fs::remove_file(&path).ok(),
+      };
+    }
+  }
+
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn record() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::write(root.join("a"), "a").unwrap();
+      fs::write(root.join("b"), "b").unwrap();
+      fs::write(root.join("c"), "c").unwrap();
+
+      let (result, changes) = super::record(|| {
+        write(&root.join("a"), "changed")?;
+        write(&root.join("a"), "changed again")?;
+        write(&root.join("b"), "b")?;
+        rename(&root.join("c"), &root.join("c2"))?;
+        write(&root.join("e/f"), "f")?;
+        remove_dir(&root.join("e"))
+      });
+
+      result.unwrap();
+
+      assert_eq!(
+        changes,
+        vec![
+          Change {
+            path: root.join("a"),
+            before: Some("a".into()),
+            after: Some("changed again".into()),
+          },
+          Change {
+            path: root.join("c"),
+            before: Some("c".into()),
+            after: None,
+          },
+          Change {
+            path: root.join("c2"),
+            before: None,
+            after: Some("c".into()),
+          },
+        ]
+      );
+
+      write(&root.join("a"), "unrecorded").unwrap();
+
+      assert!(super::record(|| Ok(())).1.is_empty());
+    });
+  }
+
+  #[test]
+  fn unreadable_file() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir(root.join("dir")).unwrap();
+      fs::write(root.join("dir/a.md"), "a").unwrap();
+      fs::write(root.join("dir/b.png"), [0xff, 0xfe]).unwrap();
+
+      let (result, changes) = super::record(|| remove_dir(&root.join("dir")));
+
+      assert!(result.is_err());
+      assert!(changes.is_empty());
+      assert_eq!(fs::read(root.join("dir/b.png")).unwrap(), [0xff, 0xfe]);
+
+      remove_dir(&root.join("dir")).unwrap();
+    });
+  }
+
+  #[test]
+  fn write_leaves_no_temporary_file() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      write(&root.join("a.md"), "a").unwrap();
+      write(&root.join("a.md"), "b").unwrap();
+
+      assert_eq!(fs::read_to_string(root.join("a.md")).unwrap(), "b");
+      assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+    });
+  }
+
+  #[test]
+  fn write_long_filename() {
+    in_temp_dir!({
+      let path = env::current_dir()
+        .unwrap()
+        .join(format!("{}.md", "a".repeat(252)));
+
+      write(&path, "a").unwrap();
+
+      assert_eq!(fs::read_to_string(path).unwrap(), "a");
+    });
+  }
+
+  #[test]
+  fn transaction() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::write(root.join("a"), "a").unwrap();
+      fs::write(root.join("b"), "b").unwrap();
+
+      let (result, changes) = super::record(|| {
+        write(&root.join("a"), "changed")?;
+
+        super::transaction(|| {
+          write(&root.join("a"), "changed again")?;
+          write(&root.join("c"), "c")?;
+          remove(&root.join("b"))?;
+          remove(&root.join("missing"))
+        })
+      });
+
+      assert!(result.is_err());
+      assert!(changes.len() == 1 && changes[0].path == root.join("a"));
+      assert_eq!(fs::read_to_string(root.join("a")).unwrap(), "changed");
+      assert_eq!(fs::read_to_string(root.join("b")).unwrap(), "b");
+      assert!(!root.join("c").exists());
+
+      super::transaction(|| write(&root.join("c"), "c")).unwrap();
+
+      assert_eq!(fs::read_to_string(root.join("c")).unwrap(), "c");
+    });
+  }
+}
@@ -0,0 +1,293 @@
+use crate::common::*;
+
+#[derive(Debug, Parser)]
+pub(crate) enum Subcommand {
+  #[clap(name = "backlinks", alias = "b")]
+  /// Open notes that link to a note
+  Backlinks { name: String },
+  #[clap(name = "config")]
+  /// Inspect the configuration
+  Config {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+  },
+  #[clap(name = "dir", alias = "d")]
+  /// Output the Zettelkasten directory path
+  Dir,
+  #[clap(name = "doctor")]
+  /// Check the vault for broken links and other inconsistencies
+  Doctor {
+    #[clap(long)]
+    /// Repair the problems that can be fixed automatically
+    fix: bool,
+  },
+  #[clap(name = "explore", alias = "e")]
+  /// Explore note links
+  Explore { name: String },
+  #[clap(name = "find", alias = "f")]
+  /// Find notes by tag, e.g. `software and not archived`, `(rust or go) and
+  /// draft` or `lang/*`
+  Find {
+    #[clap(required = true)]
+    query: Vec<String>,
+  },
+  #[clap(name = "grep", alias = "g")]
+  /// Print notes matching a full-text query, best match first
+  Grep {
+    query: String,
+    #[clap(long)]
+    /// Print at most this many notes
+    limit: Option<usize>,
+  },
+  #[clap(name = "journal", alias = "j")]
+  /// Open the daily journal note for a date, creating it if needed
+  Journal {
+    #[clap(value_parser = Period::parse)]
+    /// `YYYY-MM-DD`, `today` or `yesterday`
+    date: Period,
+  },
+  #[clap(name = "link", alias = "l")]
+  /// Link two notes
+  Link { left: String, right: String },
+  #[clap(name = "list", alias = "ls")]
+  /// List notes
+  List {
+    #[clap(long, value_parser = Query::parse)]
+    /// Only list notes matching this tag query
+    tag: Option<Query>,
+    #[clap(long)]
+    /// Only list notes with this name
+    name: Option<String>,
+    #[clap(long)]
+    /// Only list notes linking to this note
+    link: Option<String>,
+    #[clap(long, value_enum, default_value_t)]
+    /// Sort order
+    sort: ListSort,
+    #[clap(long, value_enum, default_value_t)]
+    /// Output format
+    format: Format,
+  },
+  #[clap(name = "mv")]
+  /// Rename a note and rewrite every link to it
+  Move { name: String, new_name: String },
+  #[clap(name = "new", alias = "n")]
+  /// Create a new note
+  New(NewNote),
+  #[clap(name = "open", alias = "o")]
+  /// Open a note
+  Open { name: String },
+  #[clap(name = "rm")]
+  /// Move a note to the trash, removing the links to it
+  Remove { name: String },
+  #[clap(name = "redo")]
+  /// Replay the most recently undone command
+  Redo,
+  #[clap(name = "restore")]
+  /// Restore a note from the trash, putting back the links to it
+  Restore { name: Option<String> },
+  #[clap(name = "retag")]
+  /// Rename or merge tags across the vault
+  Retag {
+    #[clap(subcommand)]
+    command: RetagCommand,
+  },
+  #[clap(name = "rmtag", alias = "rt")]
+  /// Remove a tag from a note
+  RemoveTag {
+    name: String,
+    tag: String,
+    #[clap(long, short)]
+    /// Also remove the tags nested under the tag, such as `lang/rust` when
+    /// removing `lang`
+    recursive: bool,
+  },
+  #[clap(name = "rmlink", alias = "rl")]
+  /// Remove a link between two notes
+  RemoveLink { left: String, right: String },
+  #[clap(name = "search", alias = "s")]
+  /// Fuzzy search notes, ranked by an optional full-text query
+  Search { query: Option<String> },
+  #[clap(name = "sequence", alias = "seq")]
+  /// Print the Folgezettel sequence tree
+  Sequence,
+  #[clap(name = "tag", alias = "t")]
+  /// Add a tag to a note
+  Tag { name: String, tag: String },
+  #[clap(name = "tags")]
+  /// List the tags in use with their counts and last use, flagging
+  /// near-duplicates
+  Tags {
+    #[clap(long, conflicts_with_all = ["sort", "json"])]
+    /// Show tags as a hierarchy, counting notes under each node
+    tree: bool,
+    #[clap(long, value_enum, default_value_t)]
+    /// Sort order
+    sort: TagSort,
+    #[clap(long)]
+    /// Output JSON, including the notes using each tag
+    json: bool,
+  },
+  #[clap(name = "today")]
+  /// Open today's journal note, creating it if needed
+  Today,
+  #[clap(name = "trash")]
+  /// List the notes in the trash, or empty it
+  Trash {
+    #[clap(subcommand)]
+    command: Option<TrashCommand>,
+  },
+  #[clap(name = "undo")]
+  /// Revert the most recent command that changed the vault
+  Undo,
+  #[clap(name = "vaults")]
+  /// List the configured vaults, marking the one in use
+  Vaults,
+  #[clap(name = "week")]
+  /// Open this week's journal note, creating it if needed
+  Week,
+  #[clap(name = "yesterday")]
+  /// Open yesterday's journal note, creating it if needed
+  Yesterday,
+}
+
+impl Subcommand {
+  /// Whether the changes the command makes are recorded for `zk undo`.
+  /// Commands that only read the vault aren't, so they neither load the
+  /// history nor fail on a corrupt one.
+  pub(crate) fn is_recorded(&self) -> bool {
+    match self {
+      Self::Backlinks { .. }
+      | Self::Config { .. }
+      | Self::Dir
+      | Self::Doctor { fix: false }
+      | Self::Explore { .. }
+      | Self::Find { .. }
+      | Self::Grep { .. }
+      | Self::List { .. }
+      | Self::Open { .. }
+      | Self::Redo
+      | Self::Search { .. }
+      | Self::Sequence
+      | Self::Tags { .. }
+      | Self::Trash { .. }
+      | Self::Undo
+      | Self::Vaults => false,
+      Self::Doctor { fix: true }
+      | Self::Journal { .. }
+      | Self::Link { .. }
+      | Self::Move { .. }
+      | Self::New(_)
+      | Self::Remove { .. }
+      | Self::RemoveLink { .. }
+      | Self::RemoveTag { .. }
+      | Self::Restore { .. }
+      | Self::Retag { .. }
+      | Self::Tag { .. }
+      | Self::Today
+      | Self::Week
+      | Self::Yesterday => true,
+    }
+  }
+
+  pub(crate) fn run(self, handler: Handler) -> Result<()> {
+    match self {
+      Self::Backlinks { name } => handler.backlinks(&name)?,
+      Self::Config { command } => command.run(&handler)?,
+      Self::Dir => handler.dir(),
+      Self::Doctor { fix } => handler.doctor(fix)?,
+      Self::Explore { name } => handler.explore(&name)?,
+      Self::Find { query } => handler.find(&Query::parse(&query.join(" "))?)?,
+      Self::Grep { query, limit } => handler.grep(&query, limit)?,
+      Self::Link { left, right } => handler.link(&left, &right)?,
+      Self::List {
+        tag,
+        name,
+        link,
+        sort,
+        format,
+      } => handler.list(
+        tag.as_ref(),
+        name.as_deref(),
+        link.as_deref(),
+        sort,
+        format,
+      )?,
+      Self::Move { name, new_name } => handler.rename(&name, &new_name)?,
+      Self::New(new) => handler.create(new, io::stdin())?,
+      Self::Open { name } => handler.open(&name)?,
+      Self::Remove { name } => handler.remove(&name)?,
+      Self::RemoveLink { left, right } => handler.remove_link(&left, &right)?,
+      Self::RemoveTag {
+        name,
+        tag,
+        recursive,
+      } => handler.remove_tag(&name, &tag, recursive)?,
+      Self::Redo => handler.redo()?,
+      Self::Restore { name } => handler.restore(name.as_deref())?,
+      Self::Retag { command } => command.run(&handler)?,
+      Self::Journal { date } => handler.journal(date)?,
+      Self::Search { query } => handler.search(query.as_deref())?,
+      Self::Sequence => handler.sequence()?,
+      Self::Tag { name, tag } => handler.tag(&name, &tag)?,
+      Self::Tags { tree, sort, json } => handler.tags(tree, sort, json)?,
+      Self::Today => handler.journal(Period::today())?,
+      Self::Trash { command } => match command {
+        Some(command) => command.run(&handler)?,
+        None => handler.trash()?,
+      },
+      Self::Undo => handler.undo()?,
+      Self::Vaults => handler.vaults()?,
+      Self::Week => handler.journal(Period::week())?,
+      Self::Yesterday => handler.journal(Period::yesterday())?,
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tag_note_named_like_retag_command() {
+    assert!(matches!(
+      Subcommand::try_parse_from(["zk", "tag", "rename", "draft"]),
+      Ok(Subcommand::Tag { name, tag }) if name == "rename" && tag == "draft"
+    ));
+
+    assert!(matches!(
+      Subcommand::try_parse_from(["zk", "retag", "rename", "a", "b"]),
+      Ok(Subcommand::Retag {
+        command: RetagCommand::Rename { .. }
+      })
+    ));
+  }
+
+  #[test]
+  fn read_only_commands_are_not_recorded() {
+    let recorded = |args: &[&str]| {
+      Subcommand::try_parse_from(["zk"].iter().chain(args))
+        .unwrap()
+        .is_recorded()
+    };
+
+    for args in [
+      &["list"][..],
+      &["dir"],
+      &["grep", "rust"],
+      &["doctor"],
+      &["config", "show"],
+      &["trash"],
+      &["trash", "empty"],
+      &["undo"],
+    ] {
+      assert!(!recorded(args), "{:?}", args);
+    }
+
+    for args in [&["new", "a"][..], &["doctor", "--fix"], &["tag", "a", "b"]] {
+      assert!(recorded(args), "{:?}", args);
+    }
+  }
+}
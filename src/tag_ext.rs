@@ -0,0 +1,82 @@
+/// Helpers for `/`-separated hierarchical tags such as `lang/rust/async`.
+pub(crate) trait TagExt {
+  /// The tag itself followed by each of its ancestors, from the closest to
+  /// the root.
+  fn ancestors(&self) -> Vec<&str>;
+  /// Whether the tag is `ancestor` or one of its descendants.
+  fn is_within(&self, ancestor: &str) -> bool;
+  /// The tag moved from under `from` to under `to`, if it is within `from`.
+  fn reparent(&self, from: &str, to: &str) -> Option<String>;
+}
+
+impl TagExt for str {
+  fn ancestors(&self) -> Vec<&str> {
+    let mut ancestors = vec![self];
+
+    let mut rest = self;
+
+    while let Some((parent, _)) = rest.rsplit_once('/') {
+      ancestors.push(parent);
+      rest = parent;
+    }
+
+    ancestors
+  }
+
+  fn is_within(&self, ancestor: &str) -> bool {
+    let ancestor = ancestor.trim_end_matches('/');
+
+    self == ancestor
+      || self
+        .strip_prefix(ancestor)
+        .is_some_and(|rest| rest.starts_with('/'))
+  }
+
+  fn reparent(&self, from: &str, to: &str) -> Option<String> {
+    if !self.is_within(from) {
+      return None;
+    }
+
+    Some(format!(
+      "{}{}",
+      to.trim_end_matches('/'),
+      &self[from.trim_end_matches('/').len()..]
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ancestors() {
+    assert_eq!(
+      "lang/rust/async".ancestors(),
+      ["lang/rust/async", "lang/rust", "lang"]
+    );
+    assert_eq!("software".ancestors(), ["software"]);
+  }
+
+  #[test]
+  fn is_within() {
+    assert!("lang/rust/async".is_within("lang/rust"));
+    assert!("lang/rust/async".is_within("lang"));
+    assert!("lang/rust".is_within("lang/rust"));
+    assert!("lang/rust".is_within("lang/rust/"));
+    assert!(!"lang/rust".is_within("lang/rust/async"));
+    assert!(!"lang/rustacean".is_within("lang/rust"));
+    assert!(!"language".is_within("lang"));
+  }
+
+  #[test]
+  fn reparent() {
+    assert_eq!(
+      "lang/rust".reparent("lang", "languages").unwrap(),
+      "languages/rust"
+    );
+    assert_eq!("lang".reparent("lang", "languages").unwrap(), "languages");
+    assert_eq!("rust".reparent("rust", "lang/rust").unwrap(), "lang/rust");
+    assert_eq!("language".reparent("lang", "languages"), None);
+  }
+}
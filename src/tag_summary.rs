@@ -0,0 +1,249 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TagSort {
+  /// Most used first
+  Count,
+  /// Most recently used first
+  LastUsed,
+  /// Alphabetically
+  #[default]
+  Name,
+}
+
+/// How a single tag is used across the vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct TagSummary {
+  pub(crate) tag: String,
+  pub(crate) count: usize,
+  pub(crate) last_used: Option<DateTime<Utc>>,
+  pub(crate) notes: Vec<String>,
+  /// Other tags differing only in case or pluralisation
+  pub(crate) similar: Vec<String>,
+}
+
+impl TagSummary {
+  pub(crate) fn collect(notes: &[Note], sort: TagSort) -> Vec<Self> {
+    let mut summaries = BTreeMap::<&str, TagSummary>::new();
+
+    for note in notes {
+      for tag in note.matter.tags.iter().flatten() {
+        let summary = summaries.entry(tag).or_insert_with(|| TagSummary {
+          tag: tag.to_owned(),
+          count: 0,
+          last_used: None,
+          notes: Vec::new(),
+          similar: Vec::new(),
+        });
+
+        summary.count += 1;
+        summary.last_used = summary.last_used.max(note.id.created());
+        summary.notes.push(note.id.to_string());
+      }
+    }
+
+    let mut variants = BTreeMap::<String, Vec<String>>::new();
+
+    for tag in summaries.keys() {
+      variants
+        .entry(Self::normalize(tag))
+        .or_default()
+        .push(tag.to_string());
+    }
+
+    let mut summaries = summaries
+      .into_values()
+      .map(|mut summary| {
+        summary.similar = variants[&Self::normalize(&summary.tag)]
+          .iter()
+          .filter(|tag| **tag != summary.tag)
+          .cloned()
+          .collect();
+        summary
+      })
+      .collect::<Vec<_>>();
+
+    match sort {
+      TagSort::Count => summaries.sort_by_key(|summary| Reverse(summary.count)),
+      TagSort::LastUsed => {
+        summaries.sort_by_key(|summary| Reverse(summary.last_used))
+      }
+      TagSort::Name => {}
+    }
+
+    summaries
+  }
+
+  /// Reduce a tag to a form shared by its case and plural variants, so that
+  /// `Software`, `softwares` and `software` compare equal.
+  fn normalize(tag: &str) -> String {
+    let tag = tag.to_lowercase();
+
+    if let Some(stem) = tag.strip_suffix("ies") {
+      if stem.len() > 1 {
+        return format!("{}y", stem);
+      }
+    }
+
+    if let Some(stem) = tag.strip_suffix("es") {
+      if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| stem.ends_with(suffix))
+      {
+        return stem.to_owned();
+      }
+    }
+
+    match tag.strip_suffix('s') {
+      Some(stem) if !stem.ends_with('s') && !stem.is_empty() => stem.to_owned(),
+      _ => tag,
+    }
+  }
+
+  pub(crate) fn write(
+    summaries: &[Self],
+    out: &mut impl Write,
+  ) -> io::Result<()> {
+    let width = summaries
+      .iter()
+      .map(|summary| summary.tag.chars().count())
+      .chain(iter::once("TAG".len()))
+      .max()
+      .unwrap_or_default();
+
+    writeln!(out, "{:width$}  COUNT  LAST USED", "TAG", width = width)?;
+
+    for summary in summaries {
+      let line = format!(
+        "{:width$}  {:<5}  {}",
+        summary.tag,
+        summary.count,
+        summary
+          .last_used
+          .map(|date| date.format("%Y-%m-%d").to_string())
+          .unwrap_or_default(),
+        width = width
+      );
+
+      writeln!(out, "{}", line.trim_end())?;
+    }
+
+    let mut groups = summaries
+      .iter()
+      .filter(|summary| !summary.similar.is_empty())
+      .map(|summary| {
+        let mut group = summary.similar.clone();
+        group.push(summary.tag.clone());
+        group.sort();
+        group
+      })
+      .collect::<Vec<_>>();
+
+    groups.sort();
+    groups.dedup();
+
+    if !groups.is_empty() {
+      writeln!(out)?;
+      writeln!(out, "Possible duplicates:")?;
+
+      for group in groups {
+        writeln!(out, "  {}", group.join(", "))?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn notes() -> Vec<Note> {
+    vec![
+      note("1625104269-a.md", &["software", "Libraries"], ""),
+      note("1625184326-b.md", &["software", "library"], ""),
+      note("1625104344-c.md", &["softwares", "code"], ""),
+    ]
+  }
+
+  #[test]
+  fn normalize() {
+    let cases = vec![
+      ("software", "software"),
+      ("Softwares", "software"),
+      ("libraries", "library"),
+      ("boxes", "box"),
+      ("branches", "branch"),
+      ("class", "class"),
+      ("notes", "note"),
+      ("s", "s"),
+    ];
+
+    for (tag, want) in cases {
+      assert_eq!(TagSummary::normalize(tag), want);
+    }
+  }
+
+  #[test]
+  fn collect() {
+    let summaries = TagSummary::collect(&notes(), TagSort::Count);
+
+    assert_eq!(summaries[0].tag, "software");
+    assert_eq!(summaries[0].count, 2);
+    assert_eq!(
+      summaries[0].notes,
+      vec!["1625104269-a.md", "1625184326-b.md"]
+    );
+    assert_eq!(summaries[0].similar, vec!["softwares"]);
+    assert_eq!(
+      summaries[0]
+        .last_used
+        .unwrap()
+        .format("%Y-%m-%d")
+        .to_string(),
+      "2021-07-02"
+    );
+  }
+
+  #[test]
+  fn sort() {
+    let tags = |sort| {
+      TagSummary::collect(&notes(), sort)
+        .into_iter()
+        .map(|summary| summary.tag)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      tags(TagSort::Name),
+      vec!["Libraries", "code", "library", "software", "softwares"]
+    );
+
+    assert_eq!(tags(TagSort::LastUsed)[..2], ["library", "software"]);
+  }
+
+  #[test]
+  fn write() {
+    let mut out = Vec::new();
+
+    TagSummary::write(&TagSummary::collect(&notes(), TagSort::Name), &mut out)
+      .unwrap();
+
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      indoc! {"
+        TAG        COUNT  LAST USED
+        Libraries  1      2021-07-01
+        code       1      2021-07-01
+        library    1      2021-07-02
+        software   2      2021-07-02
+        softwares  1      2021-07-01
+
+        Possible duplicates:
+          Libraries, library
+          software, softwares
+      "}
+    );
+  }
+}
@@ -0,0 +1,75 @@
+use crate::common::*;
+
+/// Tags arranged by their `/`-separated hierarchy, counting the notes tagged
+/// with each node or any of its descendants.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct TagTree {
+  count: usize,
+  children: BTreeMap<String, TagTree>,
+}
+
+impl TagTree {
+  pub(crate) fn new(notes: &[Note]) -> Self {
+    let mut tree = Self::default();
+
+    for note in notes {
+      let nodes = note
+        .matter
+        .tags
+        .iter()
+        .flatten()
+        .flat_map(|tag| tag.ancestors())
+        .collect::<BTreeSet<_>>();
+
+      for node in nodes {
+        node
+          .split('/')
+          .fold(&mut tree, |tree, segment| {
+            tree.children.entry(segment.to_owned()).or_default()
+          })
+          .count += 1;
+      }
+    }
+
+    tree
+  }
+}
+
+impl Tree for TagTree {
+  fn nodes(&self) -> Vec<(String, &Self)> {
+    self
+      .children
+      .iter()
+      .map(|(name, child)| (format!("{} ({})", name, child.count), child))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn write() {
+    let tree = TagTree::new(&[
+      note("1625104269-a.md", &["lang/rust/async", "lang/rust"], ""),
+      note("1625104269-a.md", &["lang/go", "software"], ""),
+      note("1625104269-a.md", &["lang/rust/macros"], ""),
+    ]);
+
+    let mut out = Vec::new();
+    tree.write(&mut out).unwrap();
+
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      indoc! {"
+        lang (3)
+        ├── go (1)
+        └── rust (2)
+            ├── async (1)
+            └── macros (1)
+        software (1)
+      "}
+    );
+  }
+}
@@ -0,0 +1,255 @@
+use crate::common::*;
+
+/// A note skeleton that `zk new --template` fills in, written like any other
+/// note with optional frontmatter followed by a body.
+///
+/// Placeholders are written `{{name}}`, with optional whitespace inside the
+/// braces. Unknown placeholders are left as they are. Within the frontmatter
+/// values are quoted or escaped, so that whatever they contain they are read
+/// back as the same string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+  text: String,
+}
+
+impl Template {
+  /// Load the template called `name`, looking in each of `dirs` in turn.
+  pub(crate) fn load(name: &str, dirs: &[PathBuf]) -> Result<Self> {
+    let filename = format!("{}.md", name);
+
+    for dir in dirs {
+      let path = dir.join(&filename);
+
+      if path.is_file() {
+        return Ok(Self {
+          text: fs::read_to_string(path)?,
+        });
+      }
+    }
+
+    Err(Error::TemplateNotFound {
+      name: name.to_owned(),
+      dirs: dirs.to_vec(),
+    })
+  }
+
+  /// Whether the template uses `placeholder`.
+  pub(crate) fn uses(&self, placeholder: &str) -> bool {
+    Self::placeholders(&self.text).any(|(_, key)| key == placeholder)
+  }
+
+  /// The template text with each placeholder that `value` knows replaced.
+  pub(crate) fn render(
+    &self,
+    value: impl Fn(&str) -> Option<String>,
+  ) -> String {
+    let (matter, body) = Matter::split(&self.text);
+
+    if matter.is_empty() {
+      return Self::substitute(&self.text, |_, range, key| {
+        value(key).map(|value| (range, value))
+      });
+    }
+
+    format!(
+      "---\n{}---\n{}",
+      Self::substitute(matter, |text, range, key| {
+        value(key).map(|value| Self::escape(text, range, &value))
+      }),
+      Self::substitute(body, |_, range, key| {
+        value(key).map(|value| (range, value))
+      })
+    )
+  }
+
+  /// `text` with each placeholder that `replace` returns a replacement for
+  /// replaced, along with the span it returns.
+  fn substitute(
+    text: &str,
+    replace: impl Fn(&str, Range<usize>, &str) -> Option<(Range<usize>, String)>,
+  ) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (range, key) in Self::placeholders(text) {
+      if let Some((range, value)) = replace(text, range, key) {
+        result.push_str(&text[last..range.start]);
+        result.push_str(&value);
+        last = range.end;
+      }
+    }
+
+    result.push_str(&text[last..]);
+
+    result
+  }
+
+  /// How to fill in the placeholder at `range` of the frontmatter `text` with
+  /// `value`: as a quoted string of its own, replacing any quotes already
+  /// around the placeholder, or escaped when it is part of a quoted string.
+  fn escape(
+    text: &str,
+    range: Range<usize>,
+    value: &str,
+  ) -> (Range<usize>, String) {
+    let line = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+
+    let mut quote = None;
+    let mut chars = text[line..range.start].chars();
+
+    while let Some(c) = chars.next() {
+      match quote {
+        None if c == '\'' || c == '"' => quote = Some(c),
+        Some('"') if c == '\\' => {
+          chars.next();
+        }
+        Some(q) if c == q => quote = None,
+        _ => {}
+      }
+    }
+
+    let quote = match quote {
+      Some(quote) => quote,
+      None => return (range, Self::quote(value)),
+    };
+
+    if text[..range.start].ends_with(quote)
+      && text[range.end..].starts_with(quote)
+    {
+      return (range.start - 1..range.end + 1, Self::quote(value));
+    }
+
+    let escaped = if quote == '"' {
+      let json = serde_json::Value::from(value).to_string();
+      json[1..json.len() - 1].to_owned()
+    } else {
+      value.replace('\'', "''")
+    };
+
+    (range, escaped)
+  }
+
+  /// `value` as a YAML string scalar, quoted if it would otherwise be read
+  /// as something else.
+  fn quote(value: &str) -> String {
+    serde_yaml::to_string(value)
+      .map(|yaml| yaml.trim_start_matches("---\n").trim_end().to_owned())
+      .unwrap_or_else(|_| format!("{:?}", value))
+  }
+
+  fn placeholders(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut offset = 0;
+
+    iter::from_fn(move || {
+      let start = offset + text[offset..].find("{{")?;
+      let end = start + 2 + text[start + 2..].find("}}")? + 2;
+
+      offset = end;
+
+      Some((start..end, text[start + 2..end - 2].trim()))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn load() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      fs::create_dir_all(root.join("first")).unwrap();
+      fs::create_dir_all(root.join("second")).unwrap();
+
+      fs::write(root.join("first/meeting.md"), "first").unwrap();
+      fs::write(root.join("second/meeting.md"), "second").unwrap();
+      fs::write(root.join("second/project.md"), "project").unwrap();
+
+      let dirs = vec![root.join("first"), root.join("second")];
+
+      assert_eq!(Template::load("meeting", &dirs).unwrap().text, "first");
+      assert_eq!(Template::load("project", &dirs).unwrap().text, "project");
+      assert!(matches!(
+        Template::load("literature", &dirs),
+        Err(Error::TemplateNotFound { .. })
+      ));
+    });
+  }
+
+  #[test]
+  fn render() {
+    let template = Template {
+      text: "# {{title}}\n\n{{ date }} {{unknown}} {{name}}{{name}} {{".into(),
+    };
+
+    let rendered = template.render(|key| match key {
+      "title" => Some("Weekly sync".into()),
+      "date" => Some("2021-07-01".into()),
+      "name" => Some("sync".into()),
+      _ => None,
+    });
+
+    assert_eq!(
+      rendered,
+      "# Weekly sync\n\n2021-07-01 {{unknown}} syncsync {{"
+    );
+
+    assert!(template.uses("date"));
+    assert!(!template.uses("stdin"));
+  }
+
+  #[test]
+  fn render_frontmatter() {
+    let template = Template {
+      text: indoc! {r#"
+        ---
+        name: a
+        title: {{title}}
+        tags:
+          - {{tag}}
+          - '{{title}}'
+          - "{{title}}"
+          - 'On {{title}}'
+          - "On {{title}}"
+        ---
+        # {{title}}
+      "#}
+      .into(),
+    };
+
+    for title in [
+      "Foo: bar",
+      "#1",
+      "2021-07-01",
+      "yes",
+      "'single'",
+      "\"double\"",
+      "back\\slash",
+    ] {
+      let rendered = template.render(|key| match key {
+        "title" => Some(title.into()),
+        "tag" => Some("- item".into()),
+        _ => None,
+      });
+
+      let matter = Matter::from(Matter::split(&rendered).0).unwrap();
+
+      assert_eq!(matter.title.as_deref(), Some(title), "{}", rendered);
+      assert_eq!(
+        matter.tags,
+        Some(vec![
+          "- item".to_owned(),
+          title.to_owned(),
+          title.to_owned(),
+          format!("On {}", title),
+          format!("On {}", title),
+        ]),
+        "{}",
+        rendered
+      );
+      assert!(rendered.ends_with(&format!("---\n# {}\n", title)));
+    }
+  }
+}
@@ -1,8 +1,11 @@
-use crate::common::*;
+use {crate::common::*, std::sync::Mutex};
 
 #[macro_export]
 macro_rules! in_temp_dir {
   ($body: block) => {
+    let _guard = $crate::test_utils::CWD
+      .lock()
+      .unwrap_or_else(|error| error.into_inner());
     let tempdir = TempDir::new().unwrap();
     assert!(tempdir.path().exists());
     env::set_current_dir(&tempdir.path()).unwrap();
@@ -10,10 +13,41 @@ macro_rules! in_temp_dir {
   };
 }
 
-pub(crate) fn create_note(name: &str) -> Result<Note> {
-  Note::create(env::current_dir()?.join(NoteId::new(name).to_string()))
+/// Tests share the process working directory, so those that change it must
+/// not run concurrently.
+pub(crate) static CWD: Mutex<()> = Mutex::new(());
+
+/// A handler for the vault in the current directory, selecting the first
+/// matching note rather than asking.
+pub(crate) fn handler() -> Handler {
+  Handler::new(
+    Config::default(),
+    Directory::new(env::current_dir().unwrap()),
+    Selection::First,
+  )
+}
+
+/// A note that only exists in memory, for tests that don't touch the vault.
+pub(crate) fn note(filename: &str, tags: &[&str], content: &str) -> Note {
+  Note::new(
+    PathBuf::from(filename),
+    Matter::new(
+      &NoteId::parse(filename).unwrap().name,
+      Some(tags.iter().map(|tag| tag.to_string()).collect()),
+      None,
+    ),
+    content.into(),
+  )
+  .unwrap()
 }
 
-pub(crate) fn sleep() {
-  thread::sleep(time::Duration::from_millis(1000));
+pub(crate) fn create_note(name: &str) -> Result<Note> {
+  let directory = Directory::new(env::current_dir()?);
+
+  Note::create(
+    directory
+      .path
+      .join(directory.new_id(name, IdScheme::default())?.to_string()),
+    "",
+  )
 }
@@ -0,0 +1,237 @@
+use {
+  crate::common::*,
+  rust_stemmers::{Algorithm, Stemmer},
+};
+
+/// An in-memory inverted index over note names, tags and bodies, ranked with
+/// Okapi BM25.
+pub(crate) struct TextIndex {
+  lengths: Vec<usize>,
+  postings: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl TextIndex {
+  const B: f64 = 0.75;
+  const K1: f64 = 1.2;
+
+  /// Frontmatter terms are counted this many times, so a query matching a
+  /// note's name or tags outranks a passing mention in another note's body.
+  const MATTER_WEIGHT: usize = 2;
+
+  const STOP_WORDS: &'static [&'static str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "is",
+    "it", "of", "on", "or", "that", "the", "this", "to", "was", "with",
+  ];
+
+  pub(crate) fn new(notes: &[Note]) -> Self {
+    let stemmer = Stemmer::create(Algorithm::English);
+
+    let mut lengths = Vec::with_capacity(notes.len());
+    let mut postings = HashMap::<String, Vec<(usize, usize)>>::new();
+
+    for (document, note) in notes.iter().enumerate() {
+      let matter = iter::once(note.matter.name.as_str())
+        .chain(note.matter.tags.iter().flatten().map(String::as_str))
+        .flat_map(|text| Self::tokenize(&stemmer, text))
+        .collect::<Vec<_>>();
+
+      let mut frequencies = HashMap::<String, usize>::new();
+
+      for term in &matter {
+        *frequencies.entry(term.clone()).or_default() += Self::MATTER_WEIGHT;
+      }
+
+      let body = Self::tokenize(&stemmer, &note.content);
+
+      for term in &body {
+        *frequencies.entry(term.clone()).or_default() += 1;
+      }
+
+      lengths.push(matter.len() * Self::MATTER_WEIGHT + body.len());
+
+      for (term, frequency) in frequencies {
+        postings
+          .entry(term)
+          .or_default()
+          .push((document, frequency));
+      }
+    }
+
+    Self { lengths, postings }
+  }
+
+  /// Documents matching any term of `query`, as `(index, score)` pairs with
+  /// the best match first.
+  pub(crate) fn search(&self, query: &str) -> Vec<(usize, f64)> {
+    let stemmer = Stemmer::create(Algorithm::English);
+
+    let count = self.lengths.len() as f64;
+
+    let average = self.lengths.iter().sum::<usize>() as f64 / count.max(1.0);
+
+    let mut scores = HashMap::<usize, f64>::new();
+
+    let terms = Self::tokenize(&stemmer, query)
+      .into_iter()
+      .collect::<BTreeSet<_>>();
+
+    for term in terms {
+      let postings = match self.postings.get(&term) {
+        Some(postings) => postings,
+        None => continue,
+      };
+
+      let matching = postings.len() as f64;
+
+      let idf = ((count - matching + 0.5) / (matching + 0.5) + 1.0).ln();
+
+      for &(document, frequency) in postings {
+        let frequency = frequency as f64;
+
+        let length = self.lengths[document] as f64;
+
+        let score = idf * frequency * (Self::K1 + 1.0)
+          / (frequency
+            + Self::K1 * (1.0 - Self::B + Self::B * length / average.max(1.0)));
+
+        *scores.entry(document).or_default() += score;
+      }
+    }
+
+    let mut results = scores.into_iter().collect::<Vec<_>>();
+
+    results.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    results
+  }
+
+  /// The line and column, both starting at 1, of the first word in the body
+  /// of the note source `text` that matches a term of `query`.
+  pub(crate) fn locate(text: &str, query: &str) -> Option<(usize, usize)> {
+    let stemmer = Stemmer::create(Algorithm::English);
+
+    let terms = Self::tokenize(&stemmer, query)
+      .into_iter()
+      .collect::<BTreeSet<_>>();
+
+    let (_, body) = Matter::split(text);
+
+    let skipped = text[..text.len() - body.len()].matches('\n').count();
+
+    for (index, line) in body.lines().enumerate() {
+      let mut start = None;
+
+      for (col, c) in line.chars().chain(iter::once(' ')).enumerate() {
+        match (c.is_alphanumeric(), start) {
+          (true, None) => start = Some(col),
+          (false, Some(first)) => {
+            let word = line.chars().skip(first).take(col - first);
+
+            if Self::tokenize(&stemmer, &word.collect::<String>())
+              .iter()
+              .any(|term| terms.contains(term))
+            {
+              return Some((skipped + index + 1, first + 1));
+            }
+
+            start = None;
+          }
+          _ => {}
+        }
+      }
+    }
+
+    None
+  }
+
+  /// Split `text` into lowercase, stemmed terms, dropping stop words.
+  fn tokenize(stemmer: &Stemmer, text: &str) -> Vec<String> {
+    text
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| !word.is_empty())
+      .map(str::to_lowercase)
+      .filter(|word| !Self::STOP_WORDS.contains(&word.as_str()))
+      .map(|word| stemmer.stem(&word).into_owned())
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn notes() -> Vec<Note> {
+    vec![
+      note(
+        "1625104269-a.md",
+        &["software"],
+        "Writing parsers in Rust is fun.",
+      ),
+      note(
+        "1625104326-b.md",
+        &[],
+        "Parsing, parsed, parses: a parser parses everything.",
+      ),
+      note("1625104344-c.md", &["cooking"], "A recipe for bread."),
+      note("1625104359-rust.md", &[], "Notes about the language."),
+    ]
+  }
+
+  #[test]
+  fn tokenize() {
+    assert_eq!(
+      TextIndex::tokenize(
+        &Stemmer::create(Algorithm::English),
+        "The Parsers, parsing-and RUNNING!"
+      ),
+      vec!["parser", "pars", "run"]
+    );
+  }
+
+  #[test]
+  fn stemmed_matches() {
+    let results = TextIndex::new(&notes()).search("parsing");
+
+    assert_eq!(
+      results.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+      vec![1]
+    );
+  }
+
+  #[test]
+  fn ranking() {
+    let results = TextIndex::new(&notes()).search("rust");
+
+    assert_eq!(
+      results.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+      vec![3, 0]
+    );
+
+    assert!(results[0].1 > results[1].1);
+  }
+
+  #[test]
+  fn tags() {
+    let results = TextIndex::new(&notes()).search("cooking bread");
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, 2);
+  }
+
+  #[test]
+  fn locate() {
+    let text = "---\nname: parser\n---\nIntro.\n\nWe écrit parsers here.\n";
+
+    assert_eq!(TextIndex::locate(text, "parser"), Some((6, 10)));
+    assert_eq!(TextIndex::locate(text, "intro zebra"), Some((4, 1)));
+    assert_eq!(TextIndex::locate(text, "zebra"), None);
+    assert_eq!(TextIndex::locate("Parse me", "parse"), Some((1, 1)));
+  }
+
+  #[test]
+  fn no_matches() {
+    assert!(TextIndex::new(&notes()).search("the").is_empty());
+    assert!(TextIndex::new(&notes()).search("zebra").is_empty());
+    assert!(TextIndex::new(&[]).search("rust").is_empty());
+  }
+}
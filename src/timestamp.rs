@@ -0,0 +1,85 @@
+//! Lenient (de)serialization of optional frontmatter timestamps, accepting
+//! RFC 3339 timestamps as well as plain `YYYY-MM-DD HH:MM:SS` and `YYYY-MM-DD`
+//! values, which are taken to be in UTC.
+//!
+//! Any other value, such as another tool's format, is read as no timestamp at
+//! all rather than failing the whole note. It stays in the frontmatter as it
+//! was, for `zk doctor` to report.
+
+use {
+  crate::common::*,
+  chrono::{NaiveDate, NaiveDateTime, SecondsFormat, SubsecRound},
+  serde::{Deserializer, Serializer},
+};
+
+/// The current time, at the precision timestamps are written with.
+pub(crate) fn now() -> DateTime<Utc> {
+  Utc::now().trunc_subsecs(0)
+}
+
+pub(crate) fn parse(text: &str) -> Option<DateTime<Utc>> {
+  let text = text.trim();
+
+  DateTime::parse_from_rfc3339(text)
+    .map(|datetime| datetime.with_timezone(&Utc))
+    .ok()
+    .or_else(|| {
+      NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|datetime| datetime.and_utc())
+    })
+    .or_else(|| {
+      NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+    })
+}
+
+pub(crate) fn serialize<S: Serializer>(
+  timestamp: &Option<DateTime<Utc>>,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  match timestamp {
+    Some(timestamp) => serializer
+      .serialize_str(&timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    None => serializer.serialize_none(),
+  }
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+  Ok(
+    match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+      Some(serde_yaml::Value::String(text)) => parse(&text),
+      _ => None,
+    },
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn parse() {
+    let cases = vec![
+      ("2021-07-01T01:51:09Z", Some("2021-07-01T01:51:09+00:00")),
+      (
+        "2021-07-01T03:51:09+02:00",
+        Some("2021-07-01T01:51:09+00:00"),
+      ),
+      ("2021-07-01 01:51:09", Some("2021-07-01T01:51:09+00:00")),
+      ("2021-07-01", Some("2021-07-01T00:00:00+00:00")),
+      ("yesterday", None),
+    ];
+
+    for (text, want) in cases {
+      assert_eq!(
+        super::parse(text)
+          .map(|datetime| datetime.to_rfc3339())
+          .as_deref(),
+        want
+      );
+    }
+  }
+}
@@ -0,0 +1,443 @@
+use {crate::common::*, chrono::TimeDelta};
+
+/// The vault-local trash `zk rm` moves notes into, with one directory per
+/// removed note holding the note itself and a record of the links removed
+/// along with it.
+#[derive(Debug)]
+pub(crate) struct Trash {
+  root: PathBuf,
+  dir: PathBuf,
+}
+
+/// A note in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TrashEntry {
+  /// Where the note was, relative to the vault
+  pub(crate) path: PathBuf,
+  /// When the note was trashed, to the nanosecond so that notes trashed in
+  /// quick succession keep their order
+  pub(crate) deleted: DateTime<Utc>,
+  pub(crate) links: Vec<RemovedLink>,
+  #[serde(skip)]
+  dir: PathBuf,
+}
+
+/// A link another note had to a removed note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RemovedLink {
+  /// The filename of the linking note
+  pub(crate) note: String,
+  /// Whether the link was listed in its frontmatter
+  pub(crate) matter: bool,
+  /// The linking note's body before its inline links were removed
+  pub(crate) content: Option<String>,
+}
+
+impl RemovedLink {
+  /// Remove every link `note` has to `id` in a vault with the note `names`,
+  /// remembering how to put them back.
+  pub(crate) fn remove(
+    note: &mut Note,
+    id: &NoteId,
+    names: &Names,
+  ) -> Result<Self> {
+    let name = id.to_string();
+
+    let removed = Self {
+      note: note.id.to_string(),
+      matter: note.matter.links.iter().flatten().any(|link| *link == name),
+      content: Some(note.content.clone())
+        .filter(|content| link::unlink(content, id, names) != *content),
+    };
+
+    note.remove_link(&name, names)?;
+
+    Ok(removed)
+  }
+
+  /// Put the link back into the linking note among `notes`, if it is still
+  /// there. Inline links only come back when its body hasn't changed since.
+  ///
+  /// The linking note is found by its filename, or by its id prefix if it
+  /// was renamed since, as long as no other note shares that prefix.
+  fn restore(
+    &self,
+    notes: &mut [Note],
+    id: &NoteId,
+    names: &Names,
+  ) -> Result<()> {
+    let index = match notes
+      .iter()
+      .position(|note| note.id.to_string() == self.note)
+    {
+      Some(index) => index,
+      None => {
+        let prefix = NoteId::parse(&self.note)
+          .map(|note| note.prefix)
+          .unwrap_or_default();
+
+        let mut matching = notes
+          .iter()
+          .enumerate()
+          .filter(|(_, note)| note.id.prefix == prefix)
+          .map(|(index, _)| index);
+
+        match (matching.next(), matching.next()) {
+          (Some(index), None) => index,
+          _ => return Ok(()),
+        }
+      }
+    };
+
+    let note = &mut notes[index];
+
+    let name = id.to_string();
+
+    if self.matter && !note.has_link(&name, names) {
+      note.add_link(&name, names)?;
+    }
+
+    if let Some(content) = &self.content {
+      if note.content == link::unlink(content, id, names) {
+        note.set_content(content)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl TrashEntry {
+  /// The directory in the trash holding the note.
+  pub(crate) fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// The trashed note itself.
+  pub(crate) fn note(&self) -> PathBuf {
+    self.dir.join(self.path.file_name().unwrap_or_default())
+  }
+}
+
+impl Trash {
+  fn filename() -> &'static str {
+    "entry.json"
+  }
+
+  pub(crate) fn new(directory: &Directory) -> Self {
+    Self {
+      root: directory.path.clone(),
+      dir: directory.data_dir().join("trash"),
+    }
+  }
+
+  /// Parse an age such as `30d`, given in `m`inutes, `h`ours, `d`ays or
+  /// `w`eeks.
+  pub(crate) fn parse_age(text: &str) -> Result<TimeDelta> {
+    let invalid = || Error::AgeInvalid {
+      text: text.to_owned(),
+    };
+
+    let split = text
+      .find(|c: char| !c.is_ascii_digit())
+      .ok_or_else(invalid)?;
+
+    let count = text[..split].parse::<i64>().map_err(|_| invalid())?;
+
+    match &text[split..] {
+      "m" => TimeDelta::try_minutes(count),
+      "h" => TimeDelta::try_hours(count),
+      "d" => TimeDelta::try_days(count),
+      "w" => TimeDelta::try_weeks(count),
+      _ => None,
+    }
+    .ok_or_else(invalid)
+  }
+
+  /// Move `note` into the trash along with the `links` removed from other
+  /// notes. A note trashed under the same name as one already there gets a
+  /// numbered directory of its own.
+  pub(crate) fn put(&self, note: &Note, links: Vec<RemovedLink>) -> Result<()> {
+    let stem = note.id.stem();
+
+    let dir = iter::once(self.dir.join(&stem))
+      .chain((1..).map(|n| self.dir.join(format!("{}-{}", stem, n))))
+      .find(|dir| !dir.exists())
+      .unwrap();
+
+    let entry = TrashEntry {
+      path: note
+        .path
+        .strip_prefix(&self.root)
+        .unwrap_or(&note.path)
+        .to_owned(),
+      deleted: Utc::now(),
+      links,
+      dir,
+    };
+
+    store::write(
+      &entry.dir.join(Self::filename()),
+      &serde_json::to_string_pretty(&entry)?,
+    )?;
+
+    store::rename(&note.path, &entry.note())?;
+
+    Ok(())
+  }
+
+  /// Every note in the trash, oldest first.
+  pub(crate) fn entries(&self) -> Result<Vec<TrashEntry>> {
+    if !self.dir.is_dir() {
+      return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+
+    for dir in fs::read_dir(&self.dir)? {
+      let dir = dir?.path();
+
+      let record = dir.join(Self::filename());
+
+      if !record.is_file() {
+        continue;
+      }
+
+      entries.push(TrashEntry {
+        dir,
+        ..serde_json::from_str(&fs::read_to_string(record)?)?
+      });
+    }
+
+    entries.sort_by(|a, b| (a.deleted, &a.path).cmp(&(b.deleted, &b.path)));
+
+    Ok(entries)
+  }
+
+  /// Move a note back where it was and put back the links to it from
+  /// `notes`, returning its path.
+  pub(crate) fn restore(
+    &self,
+    entry: &TrashEntry,
+    notes: &mut [Note],
+  ) -> Result<PathBuf> {
+    let path = self.root.join(&entry.path);
+
+    if path.exists() {
+      return Err(Error::NoteExists { path });
+    }
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    store::rename(&entry.note(), &path)?;
+
+    if let Some(id) = NoteId::parse(path.unwrapped_filename()) {
+      let names = Names::new(notes.iter().map(|note| &note.id).chain([&id]));
+
+      for link in &entry.links {
+        link.restore(notes, &id, &names)?;
+      }
+    }
+
+    store::remove_dir(&entry.dir)?;
+
+    Ok(path)
+  }
+
+  /// Permanently delete the notes trashed before `cutoff`, returning them.
+  pub(crate) fn empty(&self, cutoff: DateTime<Utc>) -> Result<Vec<TrashEntry>> {
+    let entries = self
+      .entries()?
+      .into_iter()
+      .filter(|entry| entry.deleted < cutoff)
+      .collect::<Vec<_>>();
+
+    for entry in &entries {
+      store::remove_dir(&entry.dir)?;
+    }
+
+    Ok(entries)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_age() {
+    assert_eq!(Trash::parse_age("30d").unwrap(), TimeDelta::days(30));
+    assert_eq!(Trash::parse_age("2w").unwrap(), TimeDelta::weeks(2));
+    assert_eq!(Trash::parse_age("12h").unwrap(), TimeDelta::hours(12));
+    assert_eq!(Trash::parse_age("90m").unwrap(), TimeDelta::minutes(90));
+
+    for text in ["", "30", "d", "30y", "-1d", "1.5d"] {
+      assert!(Trash::parse_age(text).is_err(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn put_and_restore() {
+    in_temp_dir!({
+      let mut a = create_note("a").unwrap();
+      let mut b = create_note("b").unwrap();
+      let c = create_note("c").unwrap();
+
+      a.add_link(&c.id.to_string(), &Names::default()).unwrap();
+      b.content = "See [[c]] for more.\n".into();
+      b.add_link(&a.id.to_string(), &Names::default()).unwrap();
+
+      let directory = Directory::new(env::current_dir().unwrap());
+      let trash = Trash::new(&directory);
+
+      let names = directory.names().unwrap();
+
+      let links = vec![
+        RemovedLink::remove(&mut a, &c.id, &names).unwrap(),
+        RemovedLink::remove(&mut b, &c.id, &names).unwrap(),
+      ];
+
+      assert!(links[0].matter && links[0].content.is_none());
+      assert!(!links[1].matter && links[1].content.is_some());
+
+      trash.put(&c, links).unwrap();
+
+      assert!(!c.path.exists());
+      assert_eq!(directory.notes().unwrap().len(), 2);
+      assert_eq!(
+        Note::from(b.path.clone()).unwrap().content,
+        "See c for more.\n"
+      );
+
+      let entries = trash.entries().unwrap();
+
+      assert_eq!(entries.len(), 1);
+      assert!(entries[0].note().is_file());
+
+      let path = trash
+        .restore(&entries[0], &mut directory.notes().unwrap())
+        .unwrap();
+
+      assert_eq!(path, c.path);
+      assert!(trash.entries().unwrap().is_empty());
+      assert!(Note::from(a.path.clone())
+        .unwrap()
+        .has_link(&c.id.to_string(), &Names::default()));
+      assert_eq!(
+        Note::from(b.path.clone()).unwrap().content,
+        "See [[c]] for more.\n"
+      );
+    });
+  }
+
+  #[test]
+  fn restore_existing() {
+    in_temp_dir!({
+      let a = create_note("a").unwrap();
+
+      let trash = Trash::new(&Directory::new(env::current_dir().unwrap()));
+
+      trash.put(&a, Vec::new()).unwrap();
+      fs::write(&a.path, "").unwrap();
+
+      assert!(matches!(
+        trash.restore(&trash.entries().unwrap()[0], &mut []),
+        Err(Error::NoteExists { .. })
+      ));
+    });
+  }
+
+  #[test]
+  fn put_same_name() {
+    in_temp_dir!({
+      let a = create_note("a").unwrap();
+
+      let trash = Trash::new(&Directory::new(env::current_dir().unwrap()));
+
+      for i in 0..12 {
+        fs::write(&a.path, i.to_string()).unwrap();
+        trash.put(&a, Vec::new()).unwrap();
+      }
+
+      let entries = trash.entries().unwrap();
+
+      assert_eq!(entries.len(), 12);
+
+      for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(fs::read_to_string(entry.note()).unwrap(), i.to_string());
+      }
+
+      assert!(entries[11].dir().ends_with(format!("{}-11", a.id.stem())));
+    });
+  }
+
+  #[test]
+  fn empty() {
+    in_temp_dir!({
+      let a = create_note("a").unwrap();
+      let b = create_note("b").unwrap();
+
+      let trash = Trash::new(&Directory::new(env::current_dir().unwrap()));
+
+      trash.put(&a, Vec::new()).unwrap();
+      trash.put(&b, Vec::new()).unwrap();
+
+      assert!(trash
+        .empty(Utc::now() - TimeDelta::days(30))
+        .unwrap()
+        .is_empty());
+
+      assert_eq!(
+        trash.empty(Utc::now() + TimeDelta::days(1)).unwrap().len(),
+        2
+      );
+
+      assert!(trash.entries().unwrap().is_empty());
+    });
+  }
+
+  #[test]
+  fn restore_into_note_with_shared_prefix() {
+    in_temp_dir!({
+      let root = env::current_dir().unwrap();
+
+      let note = |prefix: &str, name: &str| {
+        let path = root.join(format!("{}-{}.md", prefix, name));
+        fs::write(&path, format!("---\nname: {}\n---\n", name)).unwrap();
+        Note::from(path).unwrap()
+      };
+
+      let a = note("1625104269", "a");
+      let mut b = note("1625104269", "b");
+      let c = note("1625104326", "c");
+
+      let directory = Directory::new(root.clone());
+      let trash = Trash::new(&directory);
+
+      b.add_link(&c.id.to_string(), &Names::default()).unwrap();
+
+      let names = directory.names().unwrap();
+      let links = vec![RemovedLink::remove(&mut b, &c.id, &names).unwrap()];
+
+      trash.put(&c, links).unwrap();
+
+      trash
+        .restore(
+          &trash.entries().unwrap()[0],
+          &mut directory.notes().unwrap(),
+        )
+        .unwrap();
+
+      let names = directory.names().unwrap();
+
+      assert!(!Note::from(a.path)
+        .unwrap()
+        .has_link(&c.id.to_string(), &names));
+      assert!(Note::from(b.path)
+        .unwrap()
+        .has_link(&c.id.to_string(), &names));
+    });
+  }
+}
@@ -0,0 +1,25 @@
+use {crate::common::*, chrono::TimeDelta};
+
+#[derive(Debug, Parser)]
+pub(crate) enum TrashCommand {
+  #[clap(name = "empty")]
+  /// Permanently delete the notes in the trash, along with the undo history
+  /// that still holds them
+  Empty {
+    #[clap(long, value_name = "AGE", value_parser = Trash::parse_age)]
+    /// Only delete notes trashed longer ago than AGE, e.g. `30d` or `2w`
+    older_than: Option<TimeDelta>,
+  },
+  #[clap(name = "list")]
+  /// List the notes in the trash, oldest first
+  List,
+}
+
+impl TrashCommand {
+  pub(crate) fn run(self, handler: &Handler) -> Result<()> {
+    match self {
+      Self::Empty { older_than } => handler.empty_trash(older_than),
+      Self::List => handler.trash(),
+    }
+  }
+}
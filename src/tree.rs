@@ -0,0 +1,44 @@
+use crate::common::*;
+
+/// A tree printed one node per line, with box-drawing branches connecting
+/// the nodes below the top level.
+pub(crate) trait Tree {
+  /// The label and subtree of each child of this node, in order.
+  fn nodes(&self) -> Vec<(String, &Self)>;
+
+  fn write(&self, out: &mut impl Write) -> io::Result<()>
+  where
+    Self: Sized,
+  {
+    for (label, child) in self.nodes() {
+      writeln!(out, "{}", label)?;
+      write_children(child, out, "")?;
+    }
+
+    Ok(())
+  }
+}
+
+fn write_children(
+  tree: &impl Tree,
+  out: &mut impl Write,
+  indent: &str,
+) -> io::Result<()> {
+  let nodes = tree.nodes();
+
+  let last = nodes.len().saturating_sub(1);
+
+  for (i, (label, child)) in nodes.into_iter().enumerate() {
+    let (branch, continuation) = if i == last {
+      ("└── ", "    ")
+    } else {
+      ("├── ", "│   ")
+    };
+
+    writeln!(out, "{}{}{}", indent, branch, label)?;
+
+    write_children(child, out, &format!("{}{}", indent, continuation))?;
+  }
+
+  Ok(())
+}